@@ -0,0 +1,54 @@
+//! Converts the built-in libraries to the binary format, so the CLI reads them in place
+//! rather than parsing them on every run.
+
+#[cfg(feature = "cli")]
+#[path = "src/librarian/binary/write.rs"]
+mod write;
+
+/// Built-in libraries, see `BuiltinsLibrary`, each read from `corpus/<name>.tsv`
+/// and written to `<name>.grumpr` in the output directory.
+#[cfg(feature = "cli")]
+const LIBRARIES: &[&str] = &["google"];
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo::rerun-if-changed=build.rs");
+    println!("cargo::rerun-if-changed=src/librarian/binary/write.rs");
+
+    #[cfg(feature = "cli")]
+    for name in LIBRARIES {
+        let source = format!("corpus/{name}.tsv");
+        println!("cargo::rerun-if-changed={source}");
+        let dest = std::path::Path::new(&std::env::var("OUT_DIR")?).join(format!("{name}.grumpr"));
+        convert(std::fs::File::open(&source)?, std::fs::File::create(dest)?)
+            .map_err(|err| format!("Failed to convert {source}: {err}"))?;
+    }
+    Ok(())
+}
+
+/// Read a TSV library as the CLI does, keeping its comments as the metadata lines.
+#[cfg(feature = "cli")]
+fn convert(source: std::fs::File, dest: std::fs::File) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::{BufRead, Read};
+
+    let mut reader = std::io::BufReader::new(source);
+    let mut metadata = String::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        reader.read_until(b'\n', &mut line)?;
+        let Some(entry) = line.strip_prefix(b"# ") else {
+            break;
+        };
+        metadata.push_str(String::from_utf8_lossy(entry).trim_end_matches(['\r', '\n']));
+        metadata.push('\n');
+    }
+
+    let seeds = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(b'\t')
+        .from_reader(std::io::Cursor::new(line).chain(reader))
+        .deserialize()
+        .collect::<csv::Result<Vec<(String, u64)>>>()?;
+    write::write_seeds(std::io::BufWriter::new(dest), &seeds, &metadata, true)?;
+    Ok(())
+}
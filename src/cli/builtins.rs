@@ -1,16 +1,20 @@
 pub trait BuiltinFile {
-    fn reader(&self) -> std::io::Cursor<&'static [u8]>;
+    fn bytes(&self) -> &'static [u8];
+
+    fn reader(&self) -> std::io::Cursor<&'static [u8]> {
+        std::io::Cursor::new(self.bytes())
+    }
 }
 
 macro_rules! impl_builtin_file {
     ($struct:ident, $($field:ident => $filepath:literal),*) => {
         impl crate::cli::builtins::BuiltinFile for $struct {
-            fn reader(&self) -> std::io::Cursor<&'static [u8]> {
+            fn bytes(&self) -> &'static [u8] {
 				match self {
 					$(
 						Self::$field => {
 							include_flate::flate!(static DATA: [u8] from $filepath);
-							std::io::Cursor::new(DATA.as_slice())
+							DATA.as_slice()
 						}
 					)*
 				}
@@ -19,3 +23,19 @@ macro_rules! impl_builtin_file {
     };
 }
 pub(crate) use impl_builtin_file;
+
+/// Built-in libraries converted to the binary format by the build script, so they are read in place.
+macro_rules! impl_builtin_library {
+    ($struct:ident, $($field:ident => $filename:literal),*) => {
+        impl crate::cli::builtins::BuiltinFile for $struct {
+            fn bytes(&self) -> &'static [u8] {
+				match self {
+					$(
+						Self::$field => include_bytes!(concat!(env!("OUT_DIR"), "/", $filename)),
+					)*
+				}
+            }
+        }
+    };
+}
+pub(crate) use impl_builtin_library;
@@ -1,11 +1,12 @@
 mod enumfile;
 pub use enumfile::BuiltinOrFile;
 mod builtins;
+pub use builtins::BuiltinFile;
 mod reclap;
 use clap::{Args, Parser, Subcommand, ValueEnum};
 pub use reclap::ReClap;
 
-use crate::cli::builtins::impl_builtin_file;
+use crate::cli::builtins::{impl_builtin_file, impl_builtin_library};
 
 /// Simple program to greet a person
 #[derive(Debug, Parser)]
@@ -31,12 +32,30 @@ pub enum CmdI {
     Filter(ReClap<OptsFilter, Self>),
     /// Match a regex pattern against the library.
     Match(ReClap<OptsMatch, Self>),
+    /// Match a crossword pattern, such as "c?t*" or "[aeiou]??", against the library.
+    Cross(ReClap<OptsCross, Self>),
+    /// Complete a prefix to every word starting with it, most common first.
+    Complete(ReClap<OptsComplete, Self>),
     /// Search for anagrams of a given pattern.
     Anna(ReClap<OptsAnna, Self>),
     /// Perform a fuzzy match against the corpus.
     Fuzzy(ReClap<OptsFuzzy, Self>),
     /// Filter to words containing at least all of the given letters.
     Has(ReClap<OptsHas, Self>),
+    /// Filter to words using only the given letters, such as in a Spelling Bee.
+    Only(ReClap<OptsOnly, Self>),
+    /// Filter to words by how many times each letter is used in them.
+    Letters(ReClap<OptsLetters, Self>),
+    /// Find the answers left by the feedback on Wordle guesses.
+    Wordle(ReClap<OptsWordle, Self>),
+    /// Run a search written in the query language, such as "anna pears | match '^s'".
+    Query(ReClap<OptsQuery, Self>),
+    /// Save a copy of the results to merge with later.
+    Branch(ReClap<OptsBranch, Self>),
+    /// Swap the results with the last saved branch.
+    Swap(ReClap<OptsSwap, Self>),
+    /// Combine the last saved branch with the results.
+    Merge(ReClap<OptsMerge, Self>),
     #[command(flatten)]
     /// Final command to execute.
     Final(CmdN),
@@ -51,6 +70,8 @@ pub enum CmdN {
     Write(ReClap<OptsWrite, Self>),
     /// Display statistics about the library.
     Stats(ReClap<OptsStats, Self>),
+    /// Rank guesses in a game of Wordle by how far they narrow down the results.
+    Guess(ReClap<OptsGuess, Self>),
 }
 
 #[derive(Debug, Args)]
@@ -69,6 +90,21 @@ pub struct OptsMatch {
     pub depth: Depth,
 }
 
+#[derive(Debug, Args)]
+pub struct OptsCross {
+    /// Crossword pattern, where `?` is any letter, `*` any run of letters and `[abc]` any of those listed.
+    pub pattern: String,
+
+    #[command(flatten)]
+    pub depth: Depth,
+}
+
+#[derive(Debug, Args)]
+pub struct OptsComplete {
+    /// Prefix the words must start with.
+    pub prefix: String,
+}
+
 #[derive(Debug, Args)]
 pub struct OptsAnna {
     /// Characters of the anagram to search for.
@@ -114,6 +150,7 @@ pub struct OptsFilter {
     /// File to list of words to remove from the library.
     pub wordlist: Option<BuiltinOrFile<BuiltinsFilter>>,
     /// Keep the top N words by popularity.
+    /// Words as popular as each other are ranked by their order in the library.
     #[arg(short, long)]
     pub top: Option<usize>,
     /// Keep words that have occurred at least N times.
@@ -127,6 +164,76 @@ pub struct OptsHas {
     pub characters: String,
 }
 
+#[derive(Debug, Args)]
+pub struct OptsOnly {
+    /// Letters the words may be made of, each any number of times.
+    pub letters: String,
+    /// Letters which must each be in the words.
+    #[arg(short, long, default_value = "")]
+    pub required: String,
+    /// Fewest letters in a word.
+    #[arg(short, long, default_value_t = 0)]
+    pub min_length: usize,
+
+    #[command(flatten)]
+    pub depth: Depth,
+}
+
+#[derive(Debug, Args)]
+pub struct OptsLetters {
+    /// Letters which must not be in the words.
+    #[arg(short = 'x', long, default_value = "")]
+    pub exclude: String,
+    /// Use each letter at most once.
+    #[arg(short, long)]
+    pub unique: bool,
+    /// How many times a letter may be used, such as "s<=2", "e>=1" or "a=2".
+    #[arg(short, long, value_delimiter = ',')]
+    pub count: Vec<String>,
+
+    #[command(flatten)]
+    pub depth: Depth,
+}
+
+#[derive(Debug, Args)]
+pub struct OptsWordle {
+    /// Each guess and its marks, such as "crane:gy..g".
+    /// Marks are `g` for green, `y` for yellow and `.` for grey.
+    #[arg(required = true)]
+    pub guesses: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct OptsQuery {
+    /// Stages separated by `|`, see `grumpr::librarian::pipeline`.
+    pub query: String,
+}
+
+#[derive(Debug, Args)]
+pub struct OptsBranch {}
+
+#[derive(Debug, Args)]
+pub struct OptsSwap {}
+
+#[derive(Debug, Args)]
+pub struct OptsMerge {
+    /// Set operation, with the saved branch on the left.
+    #[arg(value_enum)]
+    pub op: SetOp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SetOp {
+    /// Grams in either.
+    Union,
+    /// Grams in both.
+    Intersection,
+    /// Grams in the branch but not the results.
+    Difference,
+    /// Grams in exactly one of them.
+    SymmetricDifference,
+}
+
 #[derive(Debug, Default, Args)]
 pub struct OptsLibrary {
     /// Path to the library file.
@@ -144,12 +251,21 @@ pub struct OptsLibrary {
     /// This will convert all words to lowercase.
     #[arg(short, long, default_value_t = false, requires = "build")]
     pub ignore_case: bool,
+    /// Name of the corpus, recorded in the metadata of the library.
+    #[arg(long, requires = "build")]
+    pub name: Option<String>,
+    /// Language of the corpus, recorded in the metadata of the library.
+    #[arg(long, requires = "build")]
+    pub language: Option<String>,
 }
 
 #[derive(Debug, ValueEnum, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum LibraryFormat {
     CSV,
     TSV,
+    /// See `grumpr::librarian::binary`, read in place without parsing.
+    Binary,
 }
 
 #[derive(Debug, Default, Args)]
@@ -169,12 +285,62 @@ pub struct OptsShow {
     /// Frequency of the word in the local library.
     #[arg(short, long)]
     pub frequency: bool,
+    /// Annotations left by the queries, such as the edits of a fuzzy match.
+    #[arg(short, long)]
+    pub notes: bool,
+    /// Order to rank the words in.
+    #[arg(short, long, value_enum, default_value_t = Sort::CountMean)]
+    pub sort: Sort,
+    /// Show only the top ranked words.
+    #[arg(short, long)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Sort {
+    /// Most occurrences first, summed over the words of a sequence.
+    Count,
+    /// Most occurrences first, averaged over the words of a sequence.
+    #[default]
+    CountMean,
+    /// Longest first.
+    Length,
+    /// Alphabetical order.
+    Lexicographic,
+    /// Index in the library.
+    Index,
+    /// Highest score first, such as the count of a completion,
+    /// otherwise the order the query found them in.
+    Score,
+    /// Fewest edits from a fuzzy pattern first, then most occurrences.
+    Distance,
+}
+
+impl From<Sort> for grumpr::librarian::SortKey {
+    fn from(sort: Sort) -> Self {
+        match sort {
+            Sort::Count => Self::Count,
+            Sort::CountMean => Self::CountMean,
+            Sort::Length => Self::Length,
+            Sort::Lexicographic => Self::Lexicographic,
+            Sort::Index => Self::Index,
+            Sort::Score => Self::Score,
+            Sort::Distance => Self::Distance,
+        }
+    }
 }
 
 #[derive(Debug, Args)]
 pub struct OptsWrite {
-    /// TODO: unimplemented
-    pub unimplemented: String,
+    /// Path to write the library to.
+    pub file: clio::Output,
+    /// Format of the library file.
+    /// When unspecified, it is determined from the file extension.
+    #[arg(short, long)]
+    pub format: Option<LibraryFormat>,
+    /// Do not include an index of roots in a binary library.
+    #[arg(long, default_value_t = false)]
+    pub no_index: bool,
 }
 
 #[derive(Debug, Args)]
@@ -183,6 +349,40 @@ pub struct OptsStats {
     pub format: StatFormat,
 }
 
+#[derive(Debug, Args)]
+pub struct OptsGuess {
+    /// How to score each guess.
+    #[arg(short, long, value_enum, default_value_t)]
+    pub strategy: Strategy,
+    /// Weight each result by its count, as the chance of it being the answer.
+    #[arg(short, long)]
+    pub weighted: bool,
+    /// Also rank words of the library which are not results, but as long as one.
+    #[arg(short, long)]
+    pub any: bool,
+    /// Number of guesses to print.
+    #[arg(short = 'n', long, default_value_t = 10)]
+    pub top: usize,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Strategy {
+    /// Most information gained from the marks, in bits.
+    #[default]
+    Information,
+    /// Fewest results expected to be left after the marks.
+    Remaining,
+}
+
+impl From<Strategy> for grumpr::librarian::query::Strategy {
+    fn from(strategy: Strategy) -> Self {
+        match strategy {
+            Strategy::Information => Self::Information,
+            Strategy::Remaining => Self::Remaining,
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Eq, ValueEnum)]
 pub enum StatFormat {
     /// Print the stats in a human-readable format.
@@ -199,9 +399,10 @@ pub enum BuiltinsLibrary {
     Google,
 }
 
-impl_builtin_file!(
+// Converted from `corpus/google.tsv` by the build script.
+impl_builtin_library!(
     BuiltinsLibrary,
-    Google => "corpus/google.tsv"
+    Google => "google.grumpr"
 );
 
 #[derive(Debug, Clone, PartialEq, Eq, ValueEnum)]
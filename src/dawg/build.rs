@@ -0,0 +1,113 @@
+use super::{Dawg, State};
+use crate::trie::Key;
+use std::collections::HashMap;
+
+/// A state on the path of the most recent key, which may still gain edges.
+#[derive(Debug, Default)]
+struct Pending {
+    byte: u8,
+    is_final: bool,
+    edges: Vec<(u8, u32)>,
+}
+
+/// Incremental construction from sorted keys, following Daciuk et al.
+/// Once a state can no longer change it is frozen, merging it with an identical frozen state if one exists.
+#[derive(Debug, Default)]
+struct Builder {
+    register: HashMap<(bool, Vec<(u8, u32)>), u32>,
+    states: Vec<State>,
+    edges: Vec<(u8, u32)>,
+}
+
+impl Builder {
+    fn freeze(&mut self, pending: Pending) -> u32 {
+        let Self {
+            register,
+            states,
+            edges,
+        } = self;
+        *register
+            .entry((pending.is_final, pending.edges))
+            .or_insert_with_key(|(is_final, state_edges)| {
+                let keys = *is_final as u32
+                    + state_edges
+                        .iter()
+                        .map(|&(_, target)| states[target as usize].keys)
+                        .sum::<u32>();
+                let edges_start = edges.len() as u32;
+                edges.extend_from_slice(state_edges);
+                states.push(State {
+                    is_final: *is_final,
+                    keys,
+                    edges_start,
+                    edges_end: edges.len() as u32,
+                });
+                (states.len() - 1) as u32
+            })
+    }
+
+    /// Freeze the path until only `len` bytes of it remain.
+    fn freeze_to(&mut self, path: &mut Vec<Pending>, len: usize) {
+        while path.len() > len + 1 {
+            let pending = path.pop().expect("Path is longer than the root");
+            let byte = pending.byte;
+            let id = self.freeze(pending);
+            path.last_mut()
+                .expect("Root is never frozen here")
+                .edges
+                .push((byte, id));
+        }
+    }
+}
+
+impl<V> Dawg<V> {
+    /// Build from keys sorted in lexicographic order.
+    /// Of any equal keys, the value of the last is kept.
+    fn from_sorted(keys: impl IntoIterator<Item = (Vec<u8>, V)>) -> Self {
+        let mut builder = Builder::default();
+        let mut path = vec![Pending::default()];
+        let mut values = Vec::new();
+        let mut prev: Option<Vec<u8>> = None;
+
+        for (key, value) in keys {
+            let common = match prev.as_deref() {
+                Some(prev) if prev == key.as_slice() => {
+                    *values.last_mut().expect("Previous key has a value") = value;
+                    continue;
+                }
+                Some(prev) => prev.iter().zip(&key).take_while(|(a, b)| a == b).count(),
+                None => 0,
+            };
+            builder.freeze_to(&mut path, common);
+            path.extend(key[common..].iter().map(|&byte| Pending {
+                byte,
+                ..Default::default()
+            }));
+            path.last_mut().expect("Path has a root").is_final = true;
+            values.push(value);
+            prev = Some(key);
+        }
+
+        builder.freeze_to(&mut path, 0);
+        let root = builder.freeze(path.pop().expect("Path has a root"));
+
+        Dawg {
+            states: builder.states,
+            edges: builder.edges,
+            values,
+            root,
+        }
+    }
+}
+
+impl<K: Key, V> FromIterator<(K, V)> for Dawg<V> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut keys = iter
+            .into_iter()
+            .map(|(key, value)| (key.as_bytes().into_iter().collect::<Vec<_>>(), value))
+            .collect::<Vec<_>>();
+        // Stable, so equal keys keep their insertion order.
+        keys.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+        Self::from_sorted(keys)
+    }
+}
@@ -0,0 +1,61 @@
+use super::DawgNode;
+
+/// Iterator over the immediate children of a node, in ascending byte order.
+pub struct Children<'a, V> {
+    parent: DawgNode<'a, V>,
+    edges: std::slice::Iter<'a, (u8, u32)>,
+    /// The rank of the next child.
+    rank: u32,
+}
+
+impl<V> Clone for Children<'_, V> {
+    fn clone(&self) -> Self {
+        Self {
+            parent: self.parent,
+            edges: self.edges.clone(),
+            rank: self.rank,
+        }
+    }
+}
+
+impl<V> std::fmt::Debug for Children<'_, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Children")
+            .field("parent", &self.parent)
+            .field("rank", &self.rank)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, V> Children<'a, V> {
+    pub fn new(parent: DawgNode<'a, V>) -> Self {
+        let state = &parent.dawg.states[parent.state as usize];
+        Self {
+            parent,
+            edges: parent.dawg.edges[state.edges_start as usize..state.edges_end as usize].iter(),
+            // A key ending at the parent sorts before all of its children.
+            rank: parent.rank + state.is_final as u32,
+        }
+    }
+}
+
+impl<'a, V> Iterator for Children<'a, V> {
+    type Item = (u8, DawgNode<'a, V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let &(byte, state) = self.edges.next()?;
+        let child = DawgNode {
+            dawg: self.parent.dawg,
+            state,
+            rank: self.rank,
+        };
+        self.rank += self.parent.dawg.states[state as usize].keys;
+        Some((byte, child))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.edges.size_hint()
+    }
+}
+
+impl<V> ExactSizeIterator for Children<'_, V> {}
@@ -0,0 +1,34 @@
+mod build;
+pub mod iter;
+mod node;
+#[cfg(test)]
+mod test;
+
+pub use node::DawgNode;
+
+/// A minimised, immutable automaton over byte keys (a directed acyclic word graph).
+///
+/// Keys sharing a suffix share the states for it, so the graph is far smaller than a trie
+/// over the same keys. A state cannot hold a value as many keys may pass through it.
+/// Instead, values are stored in key order and each state records how many keys it leads to,
+/// so the value of a key is found by counting the keys before it on the way down.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dawg<V> {
+    states: Vec<State>,
+    /// Outgoing edges of every state, sorted by byte within each state.
+    edges: Vec<(u8, u32)>,
+    /// Values in the lexicographic order of their keys.
+    values: Vec<V>,
+    root: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct State {
+    /// Whether a key ends at this state.
+    is_final: bool,
+    /// The number of keys ending at or below this state.
+    keys: u32,
+    /// The range of this state's edges in [Dawg::edges].
+    edges_start: u32,
+    edges_end: u32,
+}
@@ -0,0 +1,125 @@
+use super::{Dawg, iter::Children};
+use crate::trie::Key;
+use std::ops::{Bound, RangeBounds};
+
+/// A position in a [Dawg], reached by following a key from the root.
+/// The same state may be reached by many keys, so the node also tracks the rank of its key.
+pub struct DawgNode<'a, V> {
+    pub(super) dawg: &'a Dawg<V>,
+    pub(super) state: u32,
+    /// The number of keys which sort before any key at or below this node.
+    pub(super) rank: u32,
+}
+
+impl<V> Clone for DawgNode<'_, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<V> Copy for DawgNode<'_, V> {}
+
+impl<V> std::fmt::Debug for DawgNode<'_, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DawgNode")
+            .field("state", &self.state)
+            .field("rank", &self.rank)
+            .finish()
+    }
+}
+
+impl<'a, V> DawgNode<'a, V> {
+    /// Returns `true` if a key ends at this node.
+    #[must_use]
+    pub fn is_final(&self) -> bool {
+        self.dawg.states[self.state as usize].is_final
+    }
+
+    /// Returns the value of the key ending at this node, if any.
+    #[must_use]
+    pub fn value(&self) -> Option<&'a V> {
+        self.is_final()
+            .then(|| &self.dawg.values[self.rank as usize])
+    }
+
+    pub fn children(&self) -> Children<'a, V> {
+        Children::new(*self)
+    }
+
+    /// Returns the values of every key at or below this node, in key order.
+    #[must_use]
+    pub fn values(&self) -> &'a [V] {
+        let keys = self.dawg.states[self.state as usize].keys;
+        &self.dawg.values[self.rank as usize..(self.rank + keys) as usize]
+    }
+}
+
+impl<V> Dawg<V> {
+    #[must_use]
+    pub fn root(&self) -> DawgNode<'_, V> {
+        DawgNode {
+            dawg: self,
+            state: self.root,
+            rank: 0,
+        }
+    }
+
+    /// Returns the node reached by following `key`, if any.
+    #[must_use]
+    pub fn node<Q: Key + ?Sized>(&self, key: &Q) -> Option<DawgNode<'_, V>> {
+        key.as_bytes()
+            .into_iter()
+            .try_fold(self.root(), |node, byte| {
+                node.children()
+                    .find_map(|(b, child)| (b == byte).then_some(child))
+            })
+    }
+
+    #[must_use]
+    pub fn get<Q: Key + ?Sized>(&self, key: &Q) -> Option<&V> {
+        self.node(key)?.value()
+    }
+
+    /// Returns the values of every key starting with `prefix`, in key order.
+    #[must_use]
+    pub fn prefix<Q: Key + ?Sized>(&self, prefix: &Q) -> &[V] {
+        self.node(prefix).map_or(&[], |node| node.values())
+    }
+
+    /// Returns the values of every key within `range`, in key order.
+    #[must_use]
+    pub fn range<'q, Q: Key + ?Sized + 'q>(&self, range: impl RangeBounds<&'q Q>) -> &[V] {
+        let start = match range.start_bound() {
+            Bound::Included(key) => self.rank(*key),
+            Bound::Excluded(key) => self.rank(*key) + self.get(*key).is_some() as usize,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(key) => self.rank(*key) + self.get(*key).is_some() as usize,
+            Bound::Excluded(key) => self.rank(*key),
+            Bound::Unbounded => self.values.len(),
+        };
+        &self.values[start..end.max(start)]
+    }
+
+    /// Returns the number of keys which sort before `key`.
+    fn rank<Q: Key + ?Sized>(&self, key: &Q) -> usize {
+        let mut node = self.root();
+        for byte in key.as_bytes() {
+            let mut next = None;
+            for (b, child) in node.children() {
+                if b == byte {
+                    next = Some(child);
+                    break;
+                } else if b > byte {
+                    return child.rank as usize;
+                }
+            }
+            match next {
+                Some(child) => node = child,
+                // Every key below this node sorts before `key`.
+                None => return node.rank as usize + node.values().len(),
+            }
+        }
+        node.rank as usize
+    }
+}
@@ -0,0 +1,76 @@
+use itertools::Itertools;
+use std::ops::Bound;
+
+use super::*;
+
+#[test]
+fn get() {
+    let keys = ["abc", "abcd", "bcd", "cd", ""];
+    let dawg: Dawg<_> = keys.iter().enumerate().map(|(i, &k)| (k, i)).collect();
+
+    assert_eq!(dawg.prefix("").len(), keys.len());
+    for (value, key) in keys.iter().enumerate() {
+        assert_eq!(dawg.get(*key), Some(&value));
+    }
+    assert_eq!(dawg.get("ab"), None);
+    assert_eq!(dawg.get("abcde"), None);
+}
+
+#[test]
+fn duplicate_keys() {
+    let dawg: Dawg<_> = [("abc", 1), ("abd", 2), ("abc", 3)].into_iter().collect();
+    assert_eq!(dawg.prefix(""), [3, 2]);
+    assert_eq!(dawg.get("abc"), Some(&3));
+}
+
+#[test]
+fn shares_suffixes() {
+    let keys = ["walking", "talking", "stalking", "balking"];
+    let dawg: Dawg<_> = keys.iter().enumerate().map(|(i, &k)| (k, i)).collect();
+
+    // All keys share the "alking" suffix, so it is stored once.
+    let bytes: usize = keys.iter().map(|k| k.len()).sum();
+    assert!(dawg.edges.len() < bytes / 2);
+
+    for (value, key) in keys.iter().enumerate() {
+        assert_eq!(dawg.get(*key), Some(&value));
+    }
+}
+
+#[test]
+fn prefix() {
+    let keys = ["car", "cat", "cattle", "ca", "dog", "c"];
+    let dawg: Dawg<_> = keys.iter().map(|&k| (k, k)).collect();
+
+    assert_eq!(dawg.prefix("ca"), ["ca", "car", "cat", "cattle"]);
+    assert_eq!(dawg.prefix("").len(), keys.len());
+    assert!(dawg.prefix("cow").is_empty());
+}
+
+#[test]
+fn children_ordered() {
+    let dawg: Dawg<_> = ["zeta", "alpha", "mu", "beta"]
+        .into_iter()
+        .enumerate()
+        .map(|(i, k)| (k, i))
+        .collect();
+
+    let found_bytes = dawg.root().children().map(|(byte, _)| byte).collect_vec();
+    assert_eq!(found_bytes, b"abmz");
+}
+
+#[test]
+fn range() {
+    let keys = ["car", "cat", "cattle", "ca", "cow", "dog", "c"];
+    let dawg: Dawg<_> = keys.iter().map(|&k| (k, k)).collect();
+
+    assert_eq!(dawg.range("cat".."cow"), ["cat", "cattle"]);
+    assert_eq!(dawg.range("cat"..="cow"), ["cat", "cattle", "cow"]);
+    assert_eq!(dawg.range("cb"..), ["cow", "dog"]);
+    assert_eq!(dawg.range(.."car"), ["c", "ca"]);
+    assert_eq!(
+        dawg.range((Bound::Excluded("car"), Bound::Unbounded)),
+        ["cat", "cattle", "cow", "dog"]
+    );
+    assert!(dawg.range("dog".."cat").is_empty());
+}
@@ -1,6 +1,7 @@
 //! Solves stuff
 
+pub(crate) mod dawg;
 pub mod librarian;
 pub(crate) mod trie;
 
-pub use librarian::{Gram, Librarian, Library, Seed};
+pub use librarian::{Gram, Librarian, Library, Seed, Shelf};
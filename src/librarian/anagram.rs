@@ -1,23 +1,13 @@
-use std::collections::HashMap;
-
-use crate::{
-    Library,
-    librarian::{
-        LibGram, Result,
-        search::{MultiHeadDFA, Nest, automata},
-    },
-    trie::Trie,
+use std::{
+    collections::{HashMap, HashSet},
+    ops::RangeInclusive,
 };
+
+use crate::librarian::{LibGram, Shelf};
 use itertools::Itertools;
 
 type Histogram = HashMap<char, usize>;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub(crate) struct Anagram<'a, 'l: 'a> {
-    pub histogram: Histogram,
-    pub grams: Vec<&'a LibGram<'l>>,
-}
-
 /// Create a histogram from a pattern string.
 pub(crate) fn histogram(pattern: &str) -> Histogram {
     histogram_sorted(pattern.chars().sorted())
@@ -31,157 +21,194 @@ fn histogram_sorted(pattern: impl IntoIterator<Item = char>) -> Histogram {
     })
 }
 
-pub(crate) fn histograms<'a, 'l: 'a>(
-    library: &'l Library,
+/// Returns the characters of a gram in sorted order, which it shares with its anagrams.
+fn key(library: &dyn Shelf, lgram: &LibGram<'_>) -> String {
+    match lgram {
+        LibGram::Word(idx, ..) => library.seed(*idx).root.chars().sorted().collect(),
+        LibGram::Sequence(indices, ..) => indices
+            .iter()
+            .flat_map(|&i| library.seed(i).root.chars())
+            .sorted()
+            .collect(),
+    }
+}
+
+/// Pair each gram with its [key].
+#[cfg(not(feature = "parallel"))]
+fn keys<'a, 'l: 'a>(
+    library: &'l dyn Shelf,
     lgrams: impl IntoIterator<Item = &'a LibGram<'l>>,
-) -> HashMap<String, Anagram<'a, 'l>> {
-    histograms_by_key(lgrams.into_iter().map(|lgram| {
-        let key = match lgram {
-            LibGram::Word(idx, ..) => library.seeds[*idx].root.chars().sorted().collect(),
-            LibGram::Sequence(indices, ..) => indices
-                .iter()
-                .flat_map(|&i| library.seeds[i].root.chars())
-                .collect(),
-        };
-        (lgram, key)
-    }))
+) -> Vec<(&'a LibGram<'l>, String)> {
+    lgrams
+        .into_iter()
+        .map(|lgram| (lgram, key(library, lgram)))
+        .collect()
 }
 
-pub(crate) fn histograms_by_key<'a, 'l: 'a>(
-    keys: impl IntoIterator<Item = (&'a LibGram<'l>, String)>,
-) -> HashMap<String, Anagram<'a, 'l>> {
-    let mut anagrams: HashMap<String, Anagram<'a, 'l>> = HashMap::new();
-    for (lgram, key) in keys {
-        anagrams
-            .entry(key)
-            .or_insert_with_key(|key| Anagram {
-                histogram: histogram_sorted(key.chars()),
-                grams: Vec::with_capacity(1),
-            })
-            .grams
-            .push(lgram);
-    }
-    anagrams
+/// Pair each gram with its [key], sorting the characters of chunks of grams on each thread.
+#[cfg(feature = "parallel")]
+fn keys<'a, 'l: 'a>(
+    library: &'l dyn Shelf,
+    lgrams: impl IntoIterator<Item = &'a LibGram<'l>>,
+) -> Vec<(&'a LibGram<'l>, String)> {
+    use rayon::prelude::*;
+    let lgrams = lgrams.into_iter().collect::<Vec<_>>();
+    lgrams
+        .into_par_iter()
+        .map(|lgram| (lgram, key(library, lgram)))
+        .collect()
+}
+
+/// Returns the keys whose histogram is accepted, checking each distinct key once.
+#[cfg(not(feature = "parallel"))]
+fn accepted(
+    keys: &[(&LibGram<'_>, String)],
+    accept: impl Fn(&Histogram) -> bool + Sync,
+) -> HashSet<String> {
+    keys.iter()
+        .map(|(_, key)| key.as_str())
+        .unique()
+        .filter(|key| accept(&histogram_sorted(key.chars())))
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Returns the keys whose histogram is accepted, checking the distinct keys on each thread.
+#[cfg(feature = "parallel")]
+fn accepted(
+    keys: &[(&LibGram<'_>, String)],
+    accept: impl Fn(&Histogram) -> bool + Sync,
+) -> HashSet<String> {
+    use rayon::prelude::*;
+    let unique = keys
+        .iter()
+        .map(|(_, key)| key.as_str())
+        .collect::<HashSet<_>>();
+    unique
+        .into_par_iter()
+        .filter(|key| accept(&histogram_sorted(key.chars())))
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Keep the grams whose histogram is accepted, in their original order.
+/// Anagrams share a histogram, so it is only checked once for each group of them.
+fn filter_histograms<'a, 'l: 'a>(
+    library: &'l dyn Shelf,
+    lgrams: impl IntoIterator<Item = &'a LibGram<'l>>,
+    accept: impl Fn(&Histogram) -> bool + Sync,
+) -> impl Iterator<Item = &'a LibGram<'l>> {
+    let keys = keys(library, lgrams);
+    let accepted = accepted(&keys, accept);
+    keys.into_iter()
+        .filter_map(move |(lgram, key)| accepted.contains(&key).then_some(lgram))
+}
+
+/// Returns how many wildcards a gram needs to be made from the pattern.
+pub(crate) fn wildcards(library: &dyn Shelf, lgram: &LibGram<'_>, pattern: &Histogram) -> usize {
+    histogram_sorted(key(library, lgram).chars())
+        .iter()
+        .map(|(c, count)| count.saturating_sub(*pattern.get(c).unwrap_or(&0)))
+        .sum()
 }
 
 pub(crate) fn sorted<'a, 'l: 'a>(
-    library: &Library,
+    library: &dyn Shelf,
     lgrams: impl IntoIterator<Item = &'a LibGram<'l>>,
     pattern: &str,
 ) -> impl Iterator<Item = &'a LibGram<'l>> {
     let pattern: String = pattern.chars().sorted().collect();
     lgrams.into_iter().filter(move |lgram| match lgram {
-        LibGram::Word(idx, ..) => library.seeds[*idx]
-            .root
-            .chars()
-            .sorted()
-            .eq(pattern.chars()),
+        LibGram::Word(idx, ..) => library.seed(*idx).root.chars().sorted().eq(pattern.chars()),
         LibGram::Sequence(indices, ..) => {
-            let key = indices.iter().flat_map(|&i| library.seeds[i].root.bytes());
+            let key = indices.iter().flat_map(|&i| library.seed(i).root.bytes());
             key.sorted().eq(pattern.bytes())
         }
     })
 }
 
-pub(crate) fn trie_dfa<'l>(
-    trie: &Trie<String, &LibGram<'l>>,
-    pattern: &str,
-    depth: usize,
-) -> Result<Vec<LibGram<'l>>> {
-    debug_assert!(
-        pattern.chars().count() < 8,
-        "Anagram search is not optimized for long patterns"
-    );
-    let dfa = automata::anagram(pattern)?;
-    let search = MultiHeadDFA::new(&dfa, Nest::new(trie, depth))?;
-
-    Ok(search
-        .map(|(node, _)| {
-            node.chain()
-                .into_iter()
-                .map(|t| t.value.expect("Returned Nodes are leaves"))
-                .collect()
-        })
-        .collect())
-}
-
 pub(crate) fn partial<'a, 'l: 'a>(
-    library: &'l Library,
+    library: &'l dyn Shelf,
     lgrams: impl IntoIterator<Item = &'a LibGram<'l>>,
     pattern: &str,
     wildcards: usize,
 ) -> impl Iterator<Item = &'a LibGram<'l>> {
     let pattern_histogram = histogram(pattern);
-    let anagrams = histograms(library, lgrams);
-
-    anagrams
-        .into_values()
-        .filter(move |anagram| {
-            let mut wildcards = wildcards as isize;
-            for (c, count) in anagram.histogram.iter() {
-                let pcount = pattern_histogram.get(c).unwrap_or(&0);
-                if pcount < count {
-                    wildcards -= (count - pcount) as isize;
-                    if wildcards < 0 {
-                        return false; // Too many characters
-                    }
+
+    filter_histograms(library, lgrams, move |histogram| {
+        let mut wildcards = wildcards as isize;
+        for (c, count) in histogram.iter() {
+            let pcount = pattern_histogram.get(c).unwrap_or(&0);
+            if pcount < count {
+                wildcards -= (count - pcount) as isize;
+                if wildcards < 0 {
+                    return false; // Too many characters
                 }
             }
-            true // All characters matched (or partially matched) / we have enough wildcards
-        })
-        .flat_map(|anagram| anagram.grams)
+        }
+        true // All characters matched (or partially matched) / we have enough wildcards
+    })
 }
 
 pub(crate) fn exact<'a, 'l: 'a>(
-    library: &'l Library,
+    library: &'l dyn Shelf,
     lgrams: impl IntoIterator<Item = &'a LibGram<'l>>,
     pattern: &str,
     wildcards: usize,
 ) -> impl Iterator<Item = &'a LibGram<'l>> {
     let pattern_histogram = histogram(pattern);
-    let anagrams = histograms(library, lgrams);
-
-    anagrams
-        .into_values()
-        .filter(move |anagram| {
-            let mut wildcards = wildcards as isize;
-            for (c, count) in anagram.histogram.iter() {
-                let pcount = pattern_histogram.get(c).unwrap_or(&0);
-                if pcount < count {
-                    wildcards -= (count - pcount) as isize;
-                    if wildcards < 0 {
-                        return false; // Too many characters
-                    }
-                } else if count < pcount {
-                    return false; // Not enough characters for an exact match
+
+    filter_histograms(library, lgrams, move |histogram| {
+        let mut wildcards = wildcards as isize;
+        for (c, count) in histogram.iter() {
+            let pcount = pattern_histogram.get(c).unwrap_or(&0);
+            if pcount < count {
+                wildcards -= (count - pcount) as isize;
+                if wildcards < 0 {
+                    return false; // Too many characters
                 }
+            } else if count < pcount {
+                return false; // Not enough characters for an exact match
             }
-            wildcards == 0 // All characters matched / we have the exact right amount of wildcards
-        })
-        .flat_map(|anagram| anagram.grams)
+        }
+        wildcards == 0 // All characters matched / we have the exact right amount of wildcards
+    })
 }
 
 pub(crate) fn atleast<'a, 'l: 'a>(
-    library: &'l Library,
+    library: &'l dyn Shelf,
     lgrams: impl IntoIterator<Item = &'a LibGram<'l>>,
     pattern: &str,
 ) -> impl Iterator<Item = &'a LibGram<'l>> {
     let pattern_histogram = histogram(pattern);
-    let anagrams = histograms(library, lgrams);
-
-    anagrams
-        .into_values()
-        .filter(move |anagram| {
-            for (c, pcount) in pattern_histogram.iter() {
-                if let Some(count) = anagram.histogram.get(c) {
-                    if count < pcount {
-                        return false; // Not enough characters
-                    }
-                } else {
-                    return false; // Character not found
+
+    filter_histograms(library, lgrams, move |histogram| {
+        for (c, pcount) in pattern_histogram.iter() {
+            if let Some(count) = histogram.get(c) {
+                if count < pcount {
+                    return false; // Not enough characters
                 }
+            } else {
+                return false; // Character not found
             }
-            true // All characters matched
-        })
-        .flat_map(|anagram| anagram.grams)
+        }
+        true // All characters matched
+    })
+}
+
+/// Keep the grams with a number of each character within its bounds,
+/// and if `distinct`, none of any character more than once.
+pub(crate) fn bounded<'a, 'l: 'a>(
+    library: &'l dyn Shelf,
+    lgrams: impl IntoIterator<Item = &'a LibGram<'l>>,
+    bounds: impl IntoIterator<Item = (char, RangeInclusive<usize>)>,
+    distinct: bool,
+) -> impl Iterator<Item = &'a LibGram<'l>> {
+    let bounds = bounds.into_iter().collect_vec();
+    filter_histograms(library, lgrams, move |histogram| {
+        (!distinct || histogram.values().all(|&count| count <= 1))
+            && bounds
+                .iter()
+                .all(|(c, bound)| bound.contains(histogram.get(c).unwrap_or(&0)))
+    })
 }
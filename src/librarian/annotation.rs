@@ -0,0 +1,72 @@
+use std::ops::Range;
+
+/// Details of how a gram matched the queries which found it, see [Librarian::iter_annotated](super::Librarian::iter_annotated).
+///
+/// Each query fills in what it knows, and keeps what earlier queries found.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Annotation {
+    /// Edits from the pattern of a fuzzy query.
+    pub distance: Option<usize>,
+    /// Wildcards used to complete an anagram.
+    pub wildcards: Option<usize>,
+    /// Bytes matched by a regex, within the roots of the gram joined without spaces.
+    pub span: Option<Range<usize>>,
+    /// Relevance given by a query which ranks its results, higher first.
+    pub score: Option<f64>,
+}
+
+impl Annotation {
+    pub const fn new() -> Self {
+        Self {
+            distance: None,
+            wildcards: None,
+            span: None,
+            score: None,
+        }
+    }
+
+    /// An annotation of the edits from a fuzzy pattern.
+    #[must_use]
+    pub const fn distance(distance: usize) -> Self {
+        Self {
+            distance: Some(distance),
+            ..Self::new()
+        }
+    }
+
+    /// Returns true if no query has annotated the gram.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Take the details known by `other`, in place of any already known.
+    pub fn merge(&mut self, other: Annotation) {
+        self.distance = other.distance.or(self.distance);
+        self.wildcards = other.wildcards.or(self.wildcards);
+        self.span = other.span.or(self.span.take());
+        self.score = other.score.or(self.score);
+    }
+}
+
+impl std::fmt::Display for Annotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut sep = "";
+        if let Some(distance) = self.distance {
+            write!(f, "{sep}distance={distance}")?;
+            sep = " ";
+        }
+        if let Some(wildcards) = self.wildcards {
+            write!(f, "{sep}wildcards={wildcards}")?;
+            sep = " ";
+        }
+        if let Some(span) = &self.span {
+            write!(f, "{sep}span={}..{}", span.start, span.end)?;
+            sep = " ";
+        }
+        if let Some(score) = self.score {
+            write!(f, "{sep}score={score}")?;
+        }
+        Ok(())
+    }
+}
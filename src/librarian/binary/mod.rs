@@ -0,0 +1,359 @@
+//! A compact binary format for a [Library], which is read in place rather than parsed.
+//!
+//! All integers are little-endian and the file is laid out as:
+//!
+//! | Section  | Contents                                                                         |
+//! |----------|----------------------------------------------------------------------------------|
+//! | Header   | magic, version, checksum, seed count, arena length, index slots, metadata length |
+//! | Counts   | `u64` count of each seed                                                         |
+//! | Offsets  | `u64` start of each root in the arena, then the end of the last                  |
+//! | Arena    | UTF-8 roots back to back, padded to a multiple of 4 bytes                        |
+//! | Index    | optional `u32` hash table of seed index + 1, 0 when empty                        |
+//! | Metadata | UTF-8 lines of `key: value`, see [Metadata]                                      |
+//!
+//! The checksum is the CRC-32 of everything after the header.
+
+mod write;
+
+pub use write::{MAGIC, VERSION};
+
+use super::{Error, Library, Metadata, Result, Seed, Shelf};
+use std::{borrow::Cow, io::Write};
+use write::{HEADER, hash};
+
+/// Position and size of each section of a file, read from its header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Layout {
+    seeds: usize,
+    arena: usize,
+    slots: usize,
+    metadata: usize,
+}
+
+impl Layout {
+    fn counts(&self) -> std::ops::Range<usize> {
+        HEADER..HEADER + self.seeds * 8
+    }
+    fn offsets(&self) -> std::ops::Range<usize> {
+        let start = self.counts().end;
+        start..start + (self.seeds + 1) * 8
+    }
+    fn arena(&self) -> std::ops::Range<usize> {
+        let start = self.offsets().end;
+        start..start + self.arena
+    }
+    fn index(&self) -> std::ops::Range<usize> {
+        let start = self.offsets().end + self.arena.next_multiple_of(4);
+        start..start + self.slots * 4
+    }
+    fn metadata(&self) -> std::ops::Range<usize> {
+        let start = self.index().end;
+        start..start + self.metadata
+    }
+
+    /// Total size of the file, or `None` if it does not fit in memory.
+    fn size(&self) -> Option<usize> {
+        let counts = self.seeds.checked_mul(8)?;
+        let offsets = self.seeds.checked_add(1)?.checked_mul(8)?;
+        let arena = self.arena.checked_next_multiple_of(4)?;
+        let index = self.slots.checked_mul(4)?;
+        HEADER
+            .checked_add(counts)?
+            .checked_add(offsets)?
+            .checked_add(arena)?
+            .checked_add(index)?
+            .checked_add(self.metadata)
+    }
+
+    /// Read the header and check the file is as large as its sections.
+    /// This does not read the sections themselves, see [Layout::check].
+    fn new(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < MAGIC.len() || bytes[..MAGIC.len()] != MAGIC {
+            return Err(Error::BadMagic);
+        }
+        if bytes.len() < MAGIC.len() + 4 {
+            return Err(Error::Corrupt("truncated header"));
+        }
+        let version = read_u32(bytes, 8);
+        if version != VERSION {
+            return Err(Error::Version(version, VERSION));
+        }
+        if bytes.len() < HEADER {
+            return Err(Error::Corrupt("truncated header"));
+        }
+
+        let size = |at| usize::try_from(read_u64(bytes, at)).ok();
+        let (Some(seeds), Some(arena), Some(slots), Some(metadata)) =
+            (size(16), size(24), size(32), size(40))
+        else {
+            return Err(Error::Corrupt("section too large"));
+        };
+        let layout = Self {
+            seeds,
+            arena,
+            slots,
+            metadata,
+        };
+        match layout.size() {
+            Some(size) if size == bytes.len() => {}
+            Some(size) if size > bytes.len() => return Err(Error::Corrupt("truncated")),
+            _ => return Err(Error::Corrupt("unexpected trailing data")),
+        }
+        if slots != 0 && !slots.is_power_of_two() {
+            return Err(Error::Corrupt("index size is not a power of two"));
+        }
+        Ok(layout)
+    }
+
+    /// Check the offsets of the roots and the index, so reading the file cannot go out of bounds.
+    /// This reads a few bytes for each seed, but not the roots themselves, see [Layout::verify].
+    fn check(&self, bytes: &[u8]) -> Result<()> {
+        let offsets = &bytes[self.offsets()];
+        let mut prev = 0;
+        for index in 0..=self.seeds {
+            let offset = read_u64(offsets, index * 8);
+            if (index == 0 && offset != 0) || offset < prev {
+                return Err(Error::Corrupt("roots out of order"));
+            }
+            prev = offset;
+        }
+        if prev != self.arena as u64 {
+            return Err(Error::Corrupt("root outside of arena"));
+        }
+
+        let index = &bytes[self.index()];
+        if (0..self.slots).any(|slot| read_u32(index, slot * 4) as usize > self.seeds) {
+            return Err(Error::Corrupt("index refers to a missing seed"));
+        }
+        Ok(())
+    }
+
+    /// Check the checksum and every section of the file.
+    fn verify(&self, bytes: &[u8]) -> Result<()> {
+        let expected = read_u32(bytes, 12);
+        let found = crc32fast::hash(&bytes[HEADER..]);
+        if expected != found {
+            return Err(Error::Checksum { expected, found });
+        }
+        self.check(bytes)?;
+
+        let arena = std::str::from_utf8(&bytes[self.arena()])
+            .map_err(|_| Error::Corrupt("root is not UTF-8"))?;
+        let offsets = &bytes[self.offsets()];
+        if (0..=self.seeds)
+            .any(|index| !arena.is_char_boundary(read_u64(offsets, index * 8) as usize))
+        {
+            return Err(Error::Corrupt("root is not UTF-8"));
+        }
+
+        std::str::from_utf8(&bytes[self.metadata()])
+            .map_err(|_| Error::Corrupt("metadata is not UTF-8"))?;
+        Ok(())
+    }
+
+    /// Split the file into its sections. The bytes must have been read by [Layout::new] and [Layout::check].
+    fn view<'a>(&self, bytes: &'a [u8]) -> BinaryLibrary<'a> {
+        BinaryLibrary {
+            counts: &bytes[self.counts()],
+            offsets: &bytes[self.offsets()],
+            arena: &bytes[self.arena()],
+            index: &bytes[self.index()],
+            metadata: &bytes[self.metadata()],
+        }
+    }
+}
+
+/// A [Library] file read in place.
+///
+/// Roots and counts are decoded on access, so opening only costs verifying the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BinaryLibrary<'a> {
+    counts: &'a [u8],
+    offsets: &'a [u8],
+    arena: &'a [u8],
+    index: &'a [u8],
+    metadata: &'a [u8],
+}
+
+impl<'a> BinaryLibrary<'a> {
+    /// Verify a library file and read it in place.
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        let layout = Layout::new(bytes)?;
+        layout.verify(bytes)?;
+        Ok(layout.view(bytes))
+    }
+
+    /// Returns `true` if the file has a prebuilt index for [Shelf::find].
+    #[must_use]
+    pub fn has_index(&self) -> bool {
+        !self.index.is_empty()
+    }
+
+    /// Root of the seed at this index, empty if it is not UTF-8 in a file which was not verified.
+    ///
+    /// # Panics
+    /// If the index is out of bounds.
+    #[must_use]
+    pub fn root(&self, index: usize) -> &'a str {
+        let start = read_u64(self.offsets, index * 8) as usize;
+        let end = read_u64(self.offsets, (index + 1) * 8) as usize;
+        std::str::from_utf8(&self.arena[start..end]).unwrap_or_default()
+    }
+
+    /// The metadata lines stored in the file, empty if they are not UTF-8.
+    fn metadata_str(&self) -> &'a str {
+        std::str::from_utf8(self.metadata).unwrap_or_default()
+    }
+
+    /// Count of the seed at this index.
+    ///
+    /// # Panics
+    /// If the index is out of bounds.
+    #[must_use]
+    pub fn count(&self, index: usize) -> u64 {
+        read_u64(self.counts, index * 8)
+    }
+
+    /// Iterate over the root and count of each seed in order.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (&'a str, u64)> + '_ {
+        (0..self.len()).map(|index| (self.root(index), self.count(index)))
+    }
+}
+
+impl Shelf for BinaryLibrary<'_> {
+    fn len(&self) -> usize {
+        self.counts.len() / 8
+    }
+
+    fn seed(&self, index: usize) -> Seed<'_> {
+        Seed {
+            root: self.root(index),
+            index,
+            count: self.count(index),
+        }
+    }
+
+    /// Uses the prebuilt index when there is one, otherwise scans every seed.
+    fn find(&self, root: &str) -> Option<usize> {
+        if !self.has_index() {
+            return (0..self.len()).find(|&index| self.root(index) == root);
+        }
+
+        let slots = self.index.len() / 4;
+        let mask = slots - 1;
+        let mut slot = hash(root) as usize & mask;
+        for _ in 0..slots {
+            match read_u32(self.index, slot * 4) as usize {
+                0 => return None,
+                index if self.root(index - 1) == root => return Some(index - 1),
+                _ => slot = (slot + 1) & mask,
+            }
+        }
+        None
+    }
+
+    /// Parsed from the file on each call.
+    fn metadata(&self) -> Cow<'_, Metadata> {
+        Cow::Owned(self.metadata_str().parse().unwrap_or_default())
+    }
+}
+
+impl From<BinaryLibrary<'_>> for Library {
+    fn from(value: BinaryLibrary<'_>) -> Self {
+        let mut library = value
+            .iter()
+            .map(|(root, count)| (root.to_owned(), count))
+            .collect::<Library>();
+        *library.metadata_mut() = value.metadata().into_owned();
+        library
+    }
+}
+
+/// A [Library] file mapped into memory and read in place.
+#[cfg(feature = "mmap")]
+#[derive(Debug)]
+pub struct MappedLibrary {
+    map: memmap2::Mmap,
+    layout: Layout,
+    metadata: Metadata,
+}
+
+#[cfg(feature = "mmap")]
+impl MappedLibrary {
+    /// Map a library file, trusting its contents.
+    ///
+    /// Unlike [BinaryLibrary::new], the checksum is not verified, so opening does not read
+    /// the whole file. Only the header and the offsets of the roots are checked, so that reading
+    /// cannot go out of bounds. A damaged count is read as is and a damaged root reads as empty,
+    /// so [verify](Self::verify) a file which may have been damaged before relying on it.
+    ///
+    /// The file must not be modified while it is mapped.
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: The map is read-only and lives no longer than `Self`.
+        // As with any memory map, changing the file underneath it is undefined behaviour.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        let layout = Layout::new(&map)?;
+        layout.check(&map)?;
+        let metadata = layout.view(&map).metadata().into_owned();
+        Ok(Self {
+            map,
+            layout,
+            metadata,
+        })
+    }
+
+    /// Check the checksum and every section of the file, reading all of it.
+    pub fn verify(&self) -> Result<()> {
+        self.layout.verify(&self.map)
+    }
+
+    /// The library read in place from the map.
+    #[must_use]
+    pub fn library(&self) -> BinaryLibrary<'_> {
+        self.layout.view(&self.map)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl Shelf for MappedLibrary {
+    fn len(&self) -> usize {
+        self.layout.seeds
+    }
+
+    fn seed(&self, index: usize) -> Seed<'_> {
+        let library = self.library();
+        Seed {
+            root: library.root(index),
+            index,
+            count: library.count(index),
+        }
+    }
+
+    fn find(&self, root: &str) -> Option<usize> {
+        self.library().find(root)
+    }
+
+    fn metadata(&self) -> Cow<'_, Metadata> {
+        Cow::Borrowed(&self.metadata)
+    }
+}
+
+impl Library {
+    /// Write the library in the binary format, see [BinaryLibrary].
+    ///
+    /// With `index`, a hash table of roots is included so lookups on the file do not scan every seed.
+    pub fn write_binary(&self, writer: impl Write, index: bool) -> Result<()> {
+        let metadata = self.metadata().to_string();
+        write::write_seeds(writer, self.seeds(), &metadata, index)?;
+        Ok(())
+    }
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
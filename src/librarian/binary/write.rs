@@ -0,0 +1,98 @@
+//! Writing the binary format, see [super].
+//!
+//! This only depends on `std` and `crc32fast`, as the build script includes it
+//! to embed the built-in libraries in the binary format.
+
+use std::io::{Result, Write};
+
+/// Identifies a library file.
+pub const MAGIC: [u8; 8] = *b"GRUMPLIB";
+/// The version of the format written by this crate.
+pub const VERSION: u32 = 1;
+
+pub(crate) const HEADER: usize = 48;
+
+/// Write the root and count of each seed, followed by the lines of their metadata.
+///
+/// With `index`, a hash table of roots is included so lookups on the file do not scan every seed.
+pub(crate) fn write_seeds(
+    mut writer: impl Write,
+    seeds: &[(String, u64)],
+    metadata: &str,
+    index: bool,
+) -> Result<()> {
+    let arena = seeds.iter().map(|(root, _)| root.len()).sum::<usize>();
+    let slots = if index && !seeds.is_empty() {
+        // Slots hold the seed index + 1, with 0 left for an empty slot.
+        if seeds.len() >= u32::MAX as usize {
+            return Err(std::io::Error::other("too many seeds to index"));
+        }
+        (seeds.len() * 2).next_power_of_two()
+    } else {
+        0
+    };
+
+    let size = seeds
+        .len()
+        .checked_mul(16)
+        .and_then(|size| size.checked_add(8))
+        .and_then(|size| size.checked_add(arena.checked_next_multiple_of(4)?))
+        .and_then(|size| size.checked_add(slots.checked_mul(4)?))
+        .and_then(|size| size.checked_add(metadata.len()))
+        .ok_or_else(|| std::io::Error::other("library too large"))?;
+    let mut body = Vec::with_capacity(size);
+    for (_, count) in seeds {
+        body.extend(count.to_le_bytes());
+    }
+    let mut offset = 0u64;
+    body.extend(offset.to_le_bytes());
+    for (root, _) in seeds {
+        offset += root.len() as u64;
+        body.extend(offset.to_le_bytes());
+    }
+    for (root, _) in seeds {
+        body.extend(root.as_bytes());
+    }
+    // Pad the arena so the index is aligned within the file.
+    body.resize((HEADER + body.len()).next_multiple_of(4) - HEADER, 0);
+
+    let mut table = vec![0u32; slots];
+    if slots != 0 {
+        let mask = slots - 1;
+        for (index, (root, _)) in seeds.iter().enumerate() {
+            let mut slot = hash(root) as usize & mask;
+            loop {
+                match table[slot] {
+                    0 => {
+                        table[slot] = index as u32 + 1;
+                        break;
+                    }
+                    // The first seed with a root is the one found by lookups.
+                    other if seeds[other as usize - 1].0 == *root => break,
+                    _ => slot = (slot + 1) & mask,
+                }
+            }
+        }
+    }
+    for slot in table {
+        body.extend(slot.to_le_bytes());
+    }
+    body.extend(metadata.as_bytes());
+
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&crc32fast::hash(&body).to_le_bytes())?;
+    writer.write_all(&(seeds.len() as u64).to_le_bytes())?;
+    writer.write_all(&(arena as u64).to_le_bytes())?;
+    writer.write_all(&(slots as u64).to_le_bytes())?;
+    writer.write_all(&(metadata.len() as u64).to_le_bytes())?;
+    writer.write_all(&body)?;
+    writer.flush()
+}
+
+/// FNV-1a, stable across platforms and releases unlike [std::hash::DefaultHasher].
+pub(crate) fn hash(root: &str) -> u64 {
+    root.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
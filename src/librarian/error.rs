@@ -14,8 +14,22 @@ pub enum Error {
     DFASearch(#[from] StartError),
     #[error(transparent)]
     Regex(#[from] regex::Error),
+    #[error("Invalid pattern: {0}")]
+    Pattern(&'static str),
+    #[error("Invalid guess: {0}")]
+    Guess(&'static str),
     #[error("Failed to find any grams up to {0} differences from the pattern")]
     NoNearest(u8),
+    #[error("Not a library file")]
+    BadMagic,
+    #[error("Unsupported library version {0}, expected {1}")]
+    Version(u32, u32),
+    #[error("Library checksum mismatch, expected {expected:#010x} found {found:#010x}")]
+    Checksum { expected: u32, found: u32 },
+    #[error("Corrupt library file: {0}")]
+    Corrupt(&'static str),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
 }
 
 impl From<dense::BuildError> for Error {
@@ -1,16 +1,16 @@
-use crate::librarian::{Library, Seed};
+use crate::librarian::{Seed, Shelf};
 use itertools::Itertools;
 use std::{borrow::Cow, marker::PhantomData};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Gram<'l> {
     /// A single word
-    Word(&'l Seed),
+    Word(Seed<'l>),
     /// A sequence of words
-    Sequence(Vec<&'l Seed>),
+    Sequence(Vec<Seed<'l>>),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub(super) enum LibGram<'l> {
     /// A single word
     Word(usize, PhantomData<&'l ()>),
@@ -20,14 +20,14 @@ pub(super) enum LibGram<'l> {
 
 impl<'l> Gram<'l> {
     #[must_use]
-    pub fn word(&self) -> Option<&'l Seed> {
+    pub fn word(&self) -> Option<Seed<'l>> {
         match self {
-            Gram::Word(seed) => Some(seed),
+            Gram::Word(seed) => Some(*seed),
             Gram::Sequence(_) => None,
         }
     }
     #[must_use]
-    pub fn sequence(self) -> Option<Vec<&'l Seed>> {
+    pub fn sequence(self) -> Option<Vec<Seed<'l>>> {
         match self {
             Gram::Word(_) => None,
             Gram::Sequence(seeds) => Some(seeds),
@@ -35,7 +35,7 @@ impl<'l> Gram<'l> {
     }
 
     #[must_use]
-    pub fn seeds(self) -> Vec<&'l Seed> {
+    pub fn seeds(self) -> Vec<Seed<'l>> {
         match self {
             Gram::Word(seed) => vec![seed],
             Gram::Sequence(seeds) => seeds,
@@ -44,11 +44,15 @@ impl<'l> Gram<'l> {
 
     pub fn root(&self) -> Cow<'l, str> {
         match self {
-            Gram::Word(seed) => Cow::Borrowed(&seed.root),
+            Gram::Word(seed) => Cow::Borrowed(seed.root),
             Gram::Sequence(seeds) => Cow::Owned(seeds.iter().map(|s| &s.root).join(" ")),
         }
     }
 
+    /// Returns the number of seeds in the gram.
+    ///
+    /// A gram always has at least one seed, so it has no `is_empty`.
+    #[expect(clippy::len_without_is_empty, reason = "a gram is never empty")]
     pub fn len(&self) -> usize {
         match self {
             Gram::Word(_) => 1,
@@ -80,7 +84,7 @@ impl<'l> Gram<'l> {
 
     pub fn cmp_by_lexicographic(&self, other: &Self) -> std::cmp::Ordering {
         match (self, other) {
-            (Gram::Word(s1), Gram::Word(s2)) => s1.root.cmp(&s2.root),
+            (Gram::Word(s1), Gram::Word(s2)) => s1.root.cmp(s2.root),
             (Gram::Sequence(s1), Gram::Sequence(s2)) => {
                 s1.iter().map(|s| &s.root).cmp(s2.iter().map(|s| &s.root))
             }
@@ -126,22 +130,22 @@ impl<'l> Gram<'l> {
 impl<'l> LibGram<'l> {
     #[inline]
     #[must_use]
-    pub fn into_gram(self, library: &'l Library) -> Gram<'l> {
+    pub fn into_gram(self, library: &'l dyn Shelf) -> Gram<'l> {
         match self {
-            LibGram::Word(index, _) => Gram::Word(&library.seeds[index]),
+            LibGram::Word(index, _) => Gram::Word(library.seed(index)),
             LibGram::Sequence(indices, _) => {
-                Gram::Sequence(indices.into_iter().map(|i| &library.seeds[i]).collect())
+                Gram::Sequence(indices.into_iter().map(|i| library.seed(i)).collect())
             }
         }
     }
 
     #[inline]
     #[must_use]
-    pub fn as_gram(&self, library: &'l Library) -> Gram<'l> {
+    pub fn as_gram(&self, library: &'l dyn Shelf) -> Gram<'l> {
         match self {
-            LibGram::Word(index, _) => Gram::Word(&library.seeds[*index]),
+            LibGram::Word(index, _) => Gram::Word(library.seed(*index)),
             LibGram::Sequence(indices, _) => {
-                Gram::Sequence(indices.iter().map(|&i| &library.seeds[i]).collect())
+                Gram::Sequence(indices.iter().map(|&i| library.seed(i)).collect())
             }
         }
     }
@@ -157,14 +161,14 @@ impl<'l> LibGram<'l> {
     }
 }
 
-impl<'l> From<&'l Seed> for Gram<'l> {
-    fn from(seed: &'l Seed) -> Self {
+impl<'l> From<Seed<'l>> for Gram<'l> {
+    fn from(seed: Seed<'l>) -> Self {
         Gram::Word(seed)
     }
 }
 
-impl<'l> FromIterator<&'l Seed> for Gram<'l> {
-    fn from_iter<T: IntoIterator<Item = &'l Seed>>(iter: T) -> Self {
+impl<'l> FromIterator<Seed<'l>> for Gram<'l> {
+    fn from_iter<T: IntoIterator<Item = Seed<'l>>>(iter: T) -> Self {
         Gram::Sequence(iter.into_iter().collect()).degrade()
     }
 }
@@ -180,8 +184,8 @@ impl<'l> From<Gram<'l>> for LibGram<'l> {
     }
 }
 
-impl<'l> From<&'l Seed> for LibGram<'l> {
-    fn from(seed: &'l Seed) -> Self {
+impl<'l> From<Seed<'l>> for LibGram<'l> {
+    fn from(seed: Seed<'l>) -> Self {
         LibGram::Word(seed.index, PhantomData)
     }
 }
@@ -215,8 +219,8 @@ impl<'a, 'l> FromIterator<&'a LibGram<'l>> for LibGram<'l> {
     }
 }
 
-impl<'l> FromIterator<&'l Seed> for LibGram<'l> {
-    fn from_iter<T: IntoIterator<Item = &'l Seed>>(iter: T) -> Self {
+impl<'l> FromIterator<Seed<'l>> for LibGram<'l> {
+    fn from_iter<T: IntoIterator<Item = Seed<'l>>>(iter: T) -> Self {
         Self::Sequence(iter.into_iter().map(|s| s.index).collect(), PhantomData).degrade()
     }
 }
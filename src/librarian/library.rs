@@ -1,18 +1,186 @@
-use crate::librarian::Seed;
+use crate::librarian::{Metadata, Seed, Shelf};
+use hashbrown::{HashTable, hash_table::Entry};
+use std::{
+    borrow::Cow,
+    hash::{BuildHasher, RandomState},
+    ops::Range,
+};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 pub struct Library {
-    pub(super) seeds: Vec<Seed>,
+    /// The root and count of each seed.
+    seeds: Vec<(String, u64)>,
+    /// The index of each seed, hashed by its root.
+    /// Roots are compared against `seeds`, so each is only stored once.
+    roots: HashTable<usize>,
+    hasher: RandomState,
+    /// Whether any root is shared by several seeds, only the first of which is in `roots`.
+    repeated: bool,
+    metadata: Metadata,
+}
+
+/// The index of the seeds is derived from them, so only the seeds and metadata are compared.
+impl PartialEq for Library {
+    fn eq(&self, other: &Self) -> bool {
+        self.seeds == other.seeds && self.metadata == other.metadata
+    }
+}
+
+impl Eq for Library {}
+
+impl Library {
+    /// Iterate over the seeds in order of their index.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = Seed<'_>> + DoubleEndedIterator {
+        self.seeds
+            .iter()
+            .enumerate()
+            .map(|(index, (root, count))| Seed {
+                root,
+                index,
+                count: *count,
+            })
+    }
+
+    /// Returns the seed with this root, the first if several share it.
+    pub fn get(&self, root: &str) -> Option<Seed<'_>> {
+        self.find(root).map(|index| self.seed(index))
+    }
+
+    /// Returns `true` if the library has a seed with this root.
+    pub fn contains(&self, root: &str) -> bool {
+        self.find(root).is_some()
+    }
+
+    /// The root and count of each seed, in order of their index.
+    pub(super) fn seeds(&self) -> &[(String, u64)] {
+        &self.seeds
+    }
+
+    /// Details of how the seeds were built, kept when the library is written.
+    pub fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
+    }
+
+    /// Replace the details of how the seeds were built.
+    #[must_use]
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Add occurrences of a root, returning the index of its seed.
+    ///
+    /// A new root is appended as a seed after the others, so the indices of existing seeds never change.
+    pub fn insert(&mut self, root: impl Into<String> + AsRef<str>, count: u64) -> usize {
+        match entry(&mut self.roots, &self.hasher, &self.seeds, root.as_ref()) {
+            Entry::Occupied(entry) => {
+                let index = *entry.get();
+                let seed = &mut self.seeds[index].1;
+                *seed = seed.saturating_add(count);
+                index
+            }
+            Entry::Vacant(entry) => {
+                let index = self.seeds.len();
+                entry.insert(index);
+                self.seeds.push((root.into(), count));
+                index
+            }
+        }
+    }
+
+    /// Add the occurrences of every seed on the shelf, as if each were [inserted](Self::insert) in order.
+    ///
+    /// Its metadata is [merged](Metadata::merge) into this one's.
+    pub fn merge(&mut self, other: &dyn Shelf) {
+        self.metadata.merge(&other.metadata());
+        self.seeds.reserve(other.len());
+        for index in 0..other.len() {
+            let seed = other.seed(index);
+            self.insert(seed.root, seed.count);
+        }
+    }
+}
+
+impl Shelf for Library {
+    fn len(&self) -> usize {
+        self.seeds.len()
+    }
+
+    fn seed(&self, index: usize) -> Seed<'_> {
+        let (root, count) = &self.seeds[index];
+        Seed {
+            root,
+            index,
+            count: *count,
+        }
+    }
+
+    fn find(&self, root: &str) -> Option<usize> {
+        self.roots
+            .find(self.hasher.hash_one(root), |&index| {
+                self.seeds[index].0 == root
+            })
+            .copied()
+    }
+
+    /// Only scans the range if a root is shared by several seeds.
+    fn find_in(&self, root: &str, range: Range<usize>) -> Option<usize> {
+        match self.find(root)? {
+            index if index >= range.start => (index < range.end).then_some(index),
+            _ if !self.repeated => None,
+            _ => range.into_iter().find(|&index| self.seeds[index].0 == root),
+        }
+    }
+
+    fn metadata(&self) -> Cow<'_, Metadata> {
+        Cow::Borrowed(&self.metadata)
+    }
 }
 
 impl FromIterator<(String, u64)> for Library {
     fn from_iter<T: IntoIterator<Item = (String, u64)>>(iter: T) -> Self {
-        let mut seeds = Vec::new();
+        let mut library = Library {
+            seeds: Vec::new(),
+            roots: HashTable::new(),
+            hasher: RandomState::new(),
+            repeated: false,
+            metadata: Metadata::default(),
+        };
 
-        for (index, (root, count)) in iter.into_iter().enumerate() {
-            seeds.push(Seed { root, index, count });
+        for (root, count) in iter {
+            // The first seed with a root is the one found by lookups.
+            let index = library.seeds.len();
+            match entry(&mut library.roots, &library.hasher, &library.seeds, &root) {
+                Entry::Vacant(entry) => {
+                    entry.insert(index);
+                }
+                Entry::Occupied(_) => library.repeated = true,
+            }
+            library.seeds.push((root, count));
         }
+        library
+    }
+}
 
-        Library { seeds }
+/// Adds the occurrences of each root, see [Library::insert].
+impl Extend<(String, u64)> for Library {
+    fn extend<T: IntoIterator<Item = (String, u64)>>(&mut self, iter: T) {
+        for (root, count) in iter {
+            self.insert(root, count);
+        }
     }
 }
+
+/// The slot in the index for a root, which is vacant if no seed has it.
+fn entry<'a>(
+    roots: &'a mut HashTable<usize>,
+    hasher: &RandomState,
+    seeds: &[(String, u64)],
+    root: &str,
+) -> Entry<'a, usize> {
+    roots.entry(
+        hasher.hash_one(root),
+        |&index| seeds[index].0 == root,
+        |&index| hasher.hash_one(seeds[index].0.as_str()),
+    )
+}
@@ -0,0 +1,137 @@
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::io::{BufRead, Read, Write};
+
+/// Details of how a library was built, kept alongside its seeds.
+///
+/// It is stored as lines of `key: value`, which text libraries begin with as comments of `# key: value`.
+/// Unknown keys are skipped when read, so files written by later versions can still be read.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Metadata {
+    /// Name of the corpus.
+    pub name: Option<String>,
+    /// Files the seeds were counted from.
+    pub sources: Vec<String>,
+    /// Number of words counted, including those below the threshold.
+    pub tokens: Option<u64>,
+    /// Minimum count for a root to be kept as a seed.
+    pub threshold: Option<u64>,
+    /// Whether the roots were converted to lowercase.
+    pub case_folded: Option<bool>,
+    /// Language of the corpus, such as `en`.
+    pub language: Option<String>,
+}
+
+impl Metadata {
+    /// Returns true if nothing is known about the library.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Take in the details of a library merged into this one.
+    ///
+    /// Its sources are added to these, and its tokens to the total if both are known.
+    /// A total too large to count is no longer known.
+    pub fn merge(&mut self, other: &Metadata) {
+        for source in &other.sources {
+            if !self.sources.contains(source) {
+                self.sources.push(source.clone());
+            }
+        }
+        self.tokens = self
+            .tokens
+            .zip(other.tokens)
+            .and_then(|(a, b)| a.checked_add(b));
+    }
+
+    /// Write the metadata as the comments which begin a text library.
+    pub fn write_comments(&self, mut writer: impl Write) -> std::io::Result<()> {
+        for (key, value) in self.entries() {
+            writeln!(writer, "# {key}: {value}")?;
+        }
+        Ok(())
+    }
+
+    /// Read the comments which begin a text library.
+    ///
+    /// Each line is read whole to tell a comment from a seed, so the first seed is read too.
+    /// It is returned with the rest of the reader, from which the seeds can be read.
+    pub fn read_comments<R: BufRead>(mut reader: R) -> std::io::Result<(Self, impl BufRead)> {
+        let mut metadata = Self::default();
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            reader.read_until(b'\n', &mut line)?;
+            let Some(entry) = line.strip_prefix(b"# ") else {
+                break;
+            };
+            metadata.read_entry(&String::from_utf8_lossy(entry));
+        }
+        Ok((metadata, std::io::Cursor::new(line).chain(reader)))
+    }
+
+    /// Each key and value to store, with any line breaks in a value replaced by spaces.
+    fn entries(&self) -> Vec<(&'static str, String)> {
+        let mut entries = Vec::new();
+        let mut push = |key, value: String| entries.push((key, value.replace(['\r', '\n'], " ")));
+        if let Some(name) = &self.name {
+            push("name", name.clone());
+        }
+        for source in &self.sources {
+            push("source", source.clone());
+        }
+        if let Some(tokens) = self.tokens {
+            push("tokens", tokens.to_string());
+        }
+        if let Some(threshold) = self.threshold {
+            push("threshold", threshold.to_string());
+        }
+        if let Some(case_folded) = self.case_folded {
+            push("case-folded", case_folded.to_string());
+        }
+        if let Some(language) = &self.language {
+            push("language", language.clone());
+        }
+        entries
+    }
+
+    /// Read a line of `key: value`, skipping it if it is not understood.
+    fn read_entry(&mut self, line: &str) {
+        let Some((key, value)) = line.trim_end_matches(['\r', '\n']).split_once(": ") else {
+            return;
+        };
+        match key {
+            "name" => self.name = Some(value.to_owned()),
+            "source" => self.sources.push(value.to_owned()),
+            "tokens" => self.tokens = value.parse().ok().or(self.tokens),
+            "threshold" => self.threshold = value.parse().ok().or(self.threshold),
+            "case-folded" => self.case_folded = value.parse().ok().or(self.case_folded),
+            "language" => self.language = Some(value.to_owned()),
+            _ => {}
+        }
+    }
+}
+
+/// Lines of `key: value`, as stored in a binary library.
+impl std::fmt::Display for Metadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (key, value) in self.entries() {
+            writeln!(f, "{key}: {value}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Metadata {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut metadata = Self::default();
+        for line in s.lines() {
+            metadata.read_entry(line);
+        }
+        Ok(metadata)
+    }
+}
@@ -1,7 +1,19 @@
 mod library;
 use itertools::Itertools;
 pub use library::Library;
-use std::{collections::HashSet, iter::FusedIterator};
+pub mod binary;
+mod shelf;
+pub use shelf::{Section, Shelf};
+pub mod pipeline;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BinaryHeap, HashMap, HashSet},
+    fmt::Debug,
+    iter::FusedIterator,
+    marker::PhantomData,
+    ops::RangeInclusive,
+    sync::{Arc, OnceLock},
+};
 mod error;
 pub use error::{Error, Result};
 mod grams;
@@ -10,38 +22,88 @@ mod stats;
 pub use search::query;
 pub use stats::Stats;
 mod anagram;
+mod annotation;
+pub use annotation::Annotation;
+mod metadata;
+pub use metadata::Metadata;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod sort;
+pub use sort::SortKey;
+mod stream;
+pub use stream::Stream;
+mod wordle;
+use stream::{Found, Scan, Source, Walk};
 #[cfg(test)]
 mod test;
 pub use grams::Gram;
 use grams::LibGram;
-use regex::Regex;
+use regex::{Regex, RegexSet};
 use regex_automata::{dfa::Automaton, util::primitives::StateID};
 
 use crate::{
-    librarian::search::{MultiHeadDFA, Nest},
-    trie::Trie,
+    dawg::Dawg,
+    librarian::search::{Indexed, Meter, MultiHeadDFA, Nest, Node},
+    trie::{Key, Trie},
 };
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Seed {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Seed<'l> {
     /// The root string
-    pub root: String,
+    pub root: &'l str,
     /// The index of the root in the library
     pub index: usize,
     /// The number of occurrences of this root in the text
     pub count: u64,
 }
 
-// TODO: Become trait over the library and sublib-ref
-// Would allow for mmap the library remove the overhead of
-// cloning the library into a librarian.
-
+/// The forward search index over the grams of a librarian.
 #[derive(Debug, Clone, PartialEq, Eq)]
+enum SearchIndex {
+    /// Built on demand, and can be narrowed alongside the librarian.
+    Trie(Trie<String, usize>),
+    /// Minimised and immutable, see [Librarian::compact].
+    Dawg(Dawg<usize>),
+}
+
+#[derive(Clone)]
 pub struct Librarian<'l> {
-    library: &'l Library,
+    library: &'l dyn Shelf,
     grams: Vec<LibGram<'l>>,
+    /// Search index over the grams, mapping each key to its index in `grams`.
+    /// Built on first use and shared between clones.
+    index: OnceLock<Arc<SearchIndex>>,
+    /// Whether `index` is built as a DAWG rather than a trie, see [Librarian::compact].
+    /// Derived librarians take this on, so their own index is compact too.
+    compact: bool,
+    /// As `index`, but with every key reversed for searching by suffix.
+    trie_rev: OnceLock<Arc<Trie<Vec<u8>, usize>>>,
+    /// The limit which cut short a search producing this librarian, or any it was derived from.
+    truncated: Option<query::Limit>,
+    /// Annotations left by the queries which found the grams, shared with derived librarians.
+    notes: Arc<HashMap<LibGram<'l>, Annotation>>,
 }
 
+/// The annotation of a gram which no query has annotated.
+static UNANNOTATED: Annotation = Annotation::new();
+
+impl std::fmt::Debug for Librarian<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Librarian")
+            .field("library", &self.library)
+            .field("grams", &self.grams)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for Librarian<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(self.library, other.library) && self.grams == other.grams
+    }
+}
+
+impl Eq for Librarian<'_> {}
+
 impl<'l> Librarian<'l> {
     /// Returns the number of seeds in the librarian.
     #[must_use]
@@ -61,6 +123,23 @@ impl<'l> Librarian<'l> {
         }
     }
 
+    /// Returns an iterator over the grams with the annotations of the queries which found them.
+    pub fn iter_annotated(&self) -> impl Iterator<Item = (Gram<'l>, &Annotation)> {
+        self.grams
+            .iter()
+            .map(|lgram| (lgram.as_gram(self.library), self.note(lgram)))
+    }
+
+    /// Returns true if any of the grams has been annotated.
+    #[must_use]
+    pub fn is_annotated(&self) -> bool {
+        !self.notes.is_empty()
+            && self
+                .grams
+                .iter()
+                .any(|lgram| self.notes.contains_key(lgram))
+    }
+
     /// Return the gram at the given index of the librarian.
     #[must_use]
     pub fn get(&self, index: usize) -> Option<Gram<'l>> {
@@ -71,91 +150,266 @@ impl<'l> Librarian<'l> {
 
     /// Returns the seed associated with this root.
     #[must_use]
-    pub fn root(&self, root: &str) -> Option<&'l Seed> {
-        self.library.seeds.iter().find(|s| s.root == root)
+    pub fn root(&self, root: &str) -> Option<Seed<'l>> {
+        self.library.get(root)
     }
     /// Returns the seed at the given index of the library.
     #[must_use]
-    pub fn index(&self, index: usize) -> Option<&'l Seed> {
-        self.library.seeds.get(index)
+    pub fn index(&self, index: usize) -> Option<Seed<'l>> {
+        (index < self.library.len()).then(|| self.library.seed(index))
     }
 
-    /// Find seeds matching a regex pattern.
+    /// Returns the details of how the library was built.
+    #[must_use]
+    pub fn metadata(&self) -> Cow<'l, Metadata> {
+        self.library.metadata()
+    }
+
+    /// Returns the limit reached if a search was cut short by its [Budget](query::Budget),
+    /// in which case the librarian holds only the grams found before then.
+    #[must_use]
+    pub fn truncated(&self) -> Option<query::Limit> {
+        self.truncated
+    }
+
+    /// Find seeds matching a regex pattern, annotated with the span matched.
     pub fn search(&self, query: &query::Match<'_>) -> Result<Self> {
-        let grams = if query.depth > 0 {
-            self.search_deep(query)?
-        } else {
-            self.search_flat(query)?
+        self.collect_source(self.search_source(query, false)?, &query.budget)
+            .annotate_spans(&[query.pattern])
+    }
+
+    /// As [search](Self::search), but finding each gram as it is taken from the stream.
+    pub fn search_iter(&self, query: &query::Match<'_>) -> Result<Stream<'_, 'l>> {
+        Ok(Stream::new(
+            self.library,
+            self.search_source(query, true)?,
+            &query.budget,
+        ))
+    }
+
+    /// Find grams matching a crossword pattern, such as `c?t*`.
+    pub fn pattern(&self, query: &query::Pattern<'_>) -> Result<Self> {
+        let dfa = search::automata::pattern(query.pattern)?;
+        let source = self.walk(dfa, query.depth, &query.budget, false)?;
+        Ok(self.collect_source(source, &query.budget))
+    }
+
+    /// Find the answers left by the feedback on the guesses of a game of Wordle.
+    pub fn wordle(&self, query: &query::Wordle<'_>) -> Result<Self> {
+        let constraints = wordle::Constraints::new(query)?;
+        let dfa = regex_automata::dfa::dense::Builder::new().build(&constraints.regex())?;
+        let (found, limit) = self.search_trie(&dfa, 0, &query.budget.without_results())?;
+        let grams = anagram::bounded(self.library, &found, constraints.counts, false)
+            .cloned()
+            .collect();
+        Ok(self.child_budgeted(grams, limit, &query.budget))
+    }
+
+    /// Rank guesses in a game of Wordle by how far their marks would narrow down the grams,
+    /// which are the candidates for the answer.
+    ///
+    /// Returns the best guesses with their scores, see [Strategy](query::Strategy),
+    /// and the limit reached if the budget ran out before every guess was scored.
+    /// Ties are broken in favour of candidates, which could be the answer, and then by their order.
+    #[must_use]
+    pub fn rank_guesses(
+        &self,
+        query: &query::Guesses,
+    ) -> (Vec<(Gram<'l>, f64)>, Option<query::Limit>) {
+        let candidates = self
+            .grams
+            .iter()
+            .map(|lgram| {
+                let weight = if query.weighted {
+                    lgram.as_gram(self.library).count_mean() as f64
+                } else {
+                    1.0
+                };
+                (self.key(lgram).chars().collect_vec(), weight)
+            })
+            .collect_vec();
+
+        let mut guesses = self.grams.clone();
+        if query.any {
+            let lengths = candidates
+                .iter()
+                .map(|(letters, _)| letters.len())
+                .collect::<HashSet<_>>();
+            let known = self.grams.iter().cloned().collect::<HashSet<_>>();
+            guesses.extend(
+                (0..self.library.len())
+                    .map(|index| LibGram::Word(index, PhantomData))
+                    .filter(|lgram| {
+                        !known.contains(lgram) && lengths.contains(&self.key(lgram).chars().count())
+                    }),
+            );
+        }
+
+        let letters = guesses
+            .iter()
+            .map(|lgram| self.key(lgram).chars().collect_vec())
+            .collect_vec();
+        let (scores, limit) = wordle::scores(&letters, &candidates, query.strategy, &query.budget);
+        let best = |lhs: &f64, rhs: &f64| match query.strategy {
+            query::Strategy::Information => rhs.total_cmp(lhs),
+            query::Strategy::Remaining => lhs.total_cmp(rhs),
         };
+        // Candidates come before the other guesses, so a stable sort favours them in a tie.
+        // Only the guesses scored are zipped with a score.
+        let ranked = guesses
+            .into_iter()
+            .zip(scores)
+            .sorted_by(|(_, lhs), (_, rhs)| best(lhs, rhs))
+            .take(query.top)
+            .map(|(lgram, score)| (lgram.into_gram(self.library), score))
+            .collect();
+        (ranked, limit)
+    }
 
-        Ok(self.child(grams))
+    /// Find grams ending with a regex pattern.
+    pub fn suffix(&self, query: &query::Suffix<'_>) -> Result<Self> {
+        let trie = self.trie_rev();
+        let dfa = search::automata::suffix(query.pattern)?;
+        let mut search =
+            MultiHeadDFA::new(&dfa, Nest::new(trie, query.depth))?.metered(query.budget.clone());
+
+        // The keys are reversed, so the last gram of a sequence is found first.
+        let grams = search
+            .by_ref()
+            .map(|(node, _)| {
+                node.chain_rev()
+                    .map(|t| &self.grams[t.index().expect("Returned Nodes are leaves")])
+                    .collect()
+            })
+            .take(query.budget.results_taken())
+            .collect();
+
+        Ok(self.child_budgeted(grams, search.limit(), &query.budget))
     }
+
     /// Nearest word search
-    /// Finds the nearest word to the given pattern using the Levenshtein distance.
+    /// Finds the nearest word to the given pattern using the Levenshtein distance,
+    /// and annotates each with it.
     pub fn nearest(&self, query: &query::Nearest<'_>) -> Result<(Self, usize)> {
-        let trie = Trie::from(self);
         let (dfa, dist_fn) = search::automata::levenshtein(query.pattern, 0..=query.distance)?;
-        let lgrams = self.search_trie_state(&trie, &dfa, 0)?;
+        let (lgrams, limit) = self.search_trie_state(&dfa, 0, &query.budget.without_results())?;
         let distance_id = lgrams
             .iter()
             .min_by_key(|(_, state)| dist_fn(&dfa, *state))
             .ok_or(Error::NoNearest(query.distance))?
             .1;
 
+        let distance = dist_fn(&dfa, distance_id) as usize;
+        let grams = lgrams
+            .into_iter()
+            .filter_map(|(lgram, state_id)| (state_id == distance_id).then_some(lgram))
+            .collect_vec();
+        let notes = grams
+            .iter()
+            .map(|lgram| (lgram.clone(), Annotation::distance(distance)))
+            .collect_vec();
+
         Ok((
-            self.child(
-                lgrams
-                    .into_iter()
-                    .filter_map(|(lgram, state_id)| (state_id == distance_id).then_some(lgram))
-                    .collect(),
-            ),
-            dist_fn(&dfa, distance_id) as usize,
+            self.child_budgeted(grams, limit, &query.budget)
+                .annotate(notes),
+            distance,
         ))
     }
 
-    /// Find seeds with a Levenshtein distance to the given pattern.
+    /// Find seeds with a Levenshtein distance to the given pattern, annotated with the distance.
     pub fn distance(&self, query: &query::Distance<'_>) -> Result<Self> {
-        let trie = Trie::from(self);
-
         // Strict requires us to match all distances, then filter out for the query distances.
         // because it matches using the shortest distance.
-        let grams = if query.strict {
+        let budget = query.budget.without_results();
+        let (found, limit): Searched<(LibGram, u8)> = if query.strict {
             let (dfa, dist_fn) = search::automata::levenshtein(
                 query.pattern,
                 0..=query.distances.iter().max().copied().unwrap_or(0),
             )?;
-            let lgrams = self.search_trie_state(&trie, &dfa, 0)?;
-            lgrams
+            let (lgrams, limit) = self.search_trie_state(&dfa, 0, &budget)?;
+            let found = lgrams
                 .into_iter()
                 .filter_map(|(lgram, state)| {
                     let distance = dist_fn(&dfa, state);
-                    (query.distances.contains(&distance)).then_some(lgram)
+                    (query.distances.contains(&distance)).then_some((lgram, distance))
                 })
-                .collect()
+                .collect();
+            (found, limit)
         } else {
-            let (dfa, _) =
+            let (dfa, dist_fn) =
                 search::automata::levenshtein(query.pattern, query.distances.iter().copied())?;
-            self.search_trie(&trie, &dfa, 0)?
+            let (lgrams, limit) = self.search_trie_state(&dfa, 0, &budget)?;
+            let found = lgrams
+                .into_iter()
+                .map(|(lgram, state)| (lgram, dist_fn(&dfa, state)))
+                .collect();
+            (found, limit)
         };
 
-        Ok(self.child(grams))
+        let notes = found
+            .iter()
+            .map(|(lgram, distance)| (lgram.clone(), Annotation::distance(*distance as usize)))
+            .collect_vec();
+        let grams = found.into_iter().map(|(lgram, _)| lgram).collect();
+        Ok(self
+            .child_budgeted(grams, limit, &query.budget)
+            .annotate(notes))
     }
 
-    /// Find anagrams
+    /// Find anagrams, annotated with the wildcards used if any are allowed.
     pub fn anagrams(&self, query: &query::Anagram<'_>) -> Result<Self> {
+        let child = self.collect_source(self.anagram_source(query, false)?, &query.budget);
+        if query.wildcards == 0 {
+            return Ok(child);
+        }
+
+        let pattern = anagram::histogram(query.pattern);
+        let notes = child
+            .grams
+            .iter()
+            .map(|lgram| {
+                let wildcards = anagram::wildcards(self.library, lgram, &pattern);
+                (
+                    lgram.clone(),
+                    Annotation {
+                        wildcards: Some(wildcards),
+                        ..Annotation::new()
+                    },
+                )
+            })
+            .collect_vec();
+        Ok(child.annotate(notes))
+    }
+
+    /// As [anagrams](Self::anagrams), but finding each gram as it is taken from the stream.
+    ///
+    /// Only a deep search without wildcards for fewer than 8 characters is lazy,
+    /// the others find every anagram before the first is returned.
+    pub fn anagrams_iter(&self, query: &query::Anagram<'_>) -> Result<Stream<'_, 'l>> {
+        Ok(Stream::new(
+            self.library,
+            self.anagram_source(query, true)?,
+            &query.budget,
+        ))
+    }
+
+    fn anagram_source(
+        &self,
+        query: &query::Anagram<'_>,
+        lazy: bool,
+    ) -> Result<Box<dyn Source<'l> + '_>> {
         // Choose the anagram search method based on the query parameters.
 
-        let grams = if query.depth > 0 {
+        Ok(if query.depth > 0 {
             if query.wildcards > 0 || query.len() >= 8 {
                 // Perform a first pass to build the deep tree whilst filtering some
                 // of the certainly not matching anagrams.
-
-                let trie = Trie::from(self);
                 let dfa = search::automata::anagram_filter(query.pattern)?;
-                let first_pass = self.search_trie(&trie, &dfa, query.depth)?;
+                let (first_pass, limit) =
+                    self.search_trie(&dfa, query.depth, &query.budget.without_results())?;
 
                 // Perform an expensive anagram search on the first pass results.
-                if query.partial {
+                let grams = if query.partial {
                     anagram::partial(self.library, &first_pass, query.pattern, query.wildcards)
                         .cloned()
                         .collect()
@@ -163,12 +417,23 @@ impl<'l> Librarian<'l> {
                     anagram::exact(self.library, &first_pass, query.pattern, query.wildcards)
                         .cloned()
                         .collect()
-                }
+                };
+                Box::new(Found::new(grams, limit))
             } else {
-                let trie = Trie::from(self);
-                anagram::trie_dfa(&trie, query.pattern, query.depth)?
+                debug_assert!(
+                    query.pattern.chars().count() < 8,
+                    "Anagram search is not optimized for long patterns"
+                );
+                let dfa = search::automata::anagram(query.pattern)?;
+                self.walk(dfa, query.depth, &query.budget, lazy)?
             }
-        } else if query.wildcards > 0 {
+        } else {
+            Box::new(Found::new(self.anagrams_flat(query), None))
+        })
+    }
+
+    fn anagrams_flat(&self, query: &query::Anagram<'_>) -> Vec<LibGram<'l>> {
+        if query.wildcards > 0 {
             anagram::exact(self.library, &self.grams, query.pattern, query.wildcards)
                 .cloned()
                 .collect()
@@ -185,23 +450,80 @@ impl<'l> Librarian<'l> {
             anagram::sorted(self.library, &self.grams, query.pattern)
                 .cloned()
                 .collect()
+        }
+    }
+
+    /// Find grams starting with the given prefix.
+    /// The results are ranked by their count, most common first, which is their score.
+    pub fn complete(&self, query: &query::Prefix<'_>) -> Result<Self> {
+        let mut meter = Meter::new(query.budget.without_results());
+        let indices: Vec<usize> = match self.search_index() {
+            SearchIndex::Trie(trie) => trie
+                .prefix(query.pattern)
+                .map(|(_, &index)| index)
+                .take_while(|_| meter.visit())
+                .collect(),
+            SearchIndex::Dawg(dawg) => dawg
+                .prefix(query.pattern)
+                .iter()
+                .copied()
+                .take_while(|_| meter.visit())
+                .collect(),
         };
+        let grams = indices
+            .into_iter()
+            .map(|index| self.grams[index].clone())
+            .sorted_by_key(|lgram| std::cmp::Reverse(lgram.as_gram(self.library).count()))
+            .collect();
+        let child = self.child_budgeted(grams, meter.limit(), &query.budget);
 
-        Ok(self.child(grams))
+        let notes = child
+            .grams
+            .iter()
+            .map(|lgram| {
+                let note = Annotation {
+                    score: Some(lgram.as_gram(self.library).count() as f64),
+                    ..Annotation::new()
+                };
+                (lgram.clone(), note)
+            })
+            .collect_vec();
+        Ok(child.annotate(notes))
+    }
+
+    /// Find grams within a lexicographic range, in sorted order.
+    pub fn range(&self, query: &query::Range<'_>) -> Result<Self> {
+        let bounds = (query.start, query.end);
+        let indices: Vec<usize> = match (self.search_index(), query.reverse) {
+            (SearchIndex::Trie(trie), false) => trie.range(bounds).map(|(_, &i)| i).collect(),
+            (SearchIndex::Trie(trie), true) => trie.range(bounds).rev().map(|(_, &i)| i).collect(),
+            (SearchIndex::Dawg(dawg), false) => dawg.range(bounds).to_vec(),
+            (SearchIndex::Dawg(dawg), true) => dawg.range(bounds).iter().rev().copied().collect(),
+        };
+
+        Ok(self.child(
+            indices
+                .into_iter()
+                .map(|index| self.grams[index].clone())
+                .collect(),
+        ))
     }
 
     pub fn whitelist<'a>(&self, it: impl IntoIterator<Item = &'a str>) -> Self {
-        let whitelist = it.into_iter().collect::<HashSet<_>>();
-        self.filter(|seed| whitelist.contains(seed.root.as_str()))
+        let whitelist = self.indices(it);
+        self.filter(|seed| whitelist.contains(&seed.index))
     }
 
     pub fn blacklist<'a>(&self, it: impl IntoIterator<Item = &'a str>) -> Self {
-        let blacklist = it.into_iter().collect::<HashSet<_>>();
-        self.filter(|seed| !blacklist.contains(seed.root.as_str()))
+        let blacklist = self.indices(it);
+        self.filter(|seed| !blacklist.contains(&seed.index))
     }
 
-    pub fn filter<'a>(&self, f: impl FnMut(&'l Seed) -> bool) -> Self {
-        self.child(self.filter_seed(f).collect())
+    pub fn filter(&self, mut f: impl FnMut(Seed<'l>) -> bool) -> Self {
+        self.child_filtered(|lgram| match lgram.as_gram(self.library) {
+            Gram::Word(seed) => f(seed),
+            Gram::Sequence(seeds) => seeds.into_iter().all(&mut f),
+        })
     }
 
     pub fn has(&self, query: &query::Has<'_>) -> Result<Self> {
@@ -212,21 +534,265 @@ impl<'l> Librarian<'l> {
         ))
     }
 
+    /// Find grams made only of letters from a bank, annotated with their score.
+    pub fn only(&self, query: &query::Only<'_>) -> Result<Self> {
+        let dfa = search::automata::only(query.letters, query.min_length)?;
+        let (found, limit) =
+            self.search_trie(&dfa, query.depth, &query.budget.without_results())?;
+        let grams = anagram::atleast(self.library, &found, query.required)
+            .cloned()
+            .collect();
+        let child = self.child_budgeted(grams, limit, &query.budget);
+
+        let bank = query.letters.chars().collect::<HashSet<_>>();
+        let notes = child
+            .grams
+            .iter()
+            .map(|lgram| {
+                let key = self.key(lgram);
+                let length = key.chars().count();
+                let mut score = if query.min_length > 0 && length == query.min_length {
+                    1
+                } else {
+                    length
+                };
+                if key.chars().collect::<HashSet<_>>() == bank {
+                    score += bank.len();
+                }
+                let note = Annotation {
+                    score: Some(score as f64),
+                    ..Annotation::new()
+                };
+                (lgram.clone(), note)
+            })
+            .collect_vec();
+        Ok(child.annotate(notes))
+    }
+
+    /// Find grams by how many times each letter is used in them.
+    pub fn letters(&self, query: &query::Letters) -> Result<Self> {
+        let dfa = if query.distinct {
+            search::automata::letters(&self.distinct_counts(&query.counts))?
+        } else {
+            search::automata::letters(&query.counts)?
+        };
+        let (found, limit) =
+            self.search_trie(&dfa, query.depth, &query.budget.without_results())?;
+        let bounds = query.counts.iter().map(|(&c, range)| (c, range.clone()));
+        let grams = anagram::bounded(self.library, &found, bounds, query.distinct)
+            .cloned()
+            .collect();
+        Ok(self.child_budgeted(grams, limit, &query.budget))
+    }
+
+    /// Grams in either librarian: those of `self`, followed by those only in `other`.
+    ///
+    /// # Panics
+    /// If the librarians do not share a library.
+    #[must_use]
+    pub fn union(&self, other: &Self) -> Self {
+        self.assert_same_library(other);
+        let mut seen = self.grams.iter().collect::<HashSet<_>>();
+        let grams = self
+            .grams
+            .iter()
+            .chain(other.grams.iter().filter(|lgram| seen.insert(lgram)))
+            .cloned()
+            .collect();
+        self.child(grams).inherit(other)
+    }
+
+    /// Grams of `self` which are also in `other`, in the order of `self`.
+    ///
+    /// # Panics
+    /// If the librarians do not share a library.
+    #[must_use]
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.assert_same_library(other);
+        let grams = other.grams.iter().collect::<HashSet<_>>();
+        self.child_filtered(|lgram| grams.contains(lgram))
+            .inherit(other)
+    }
+
+    /// Grams of `self` which are not in `other`, in the order of `self`.
+    ///
+    /// # Panics
+    /// If the librarians do not share a library.
+    #[must_use]
+    pub fn difference(&self, other: &Self) -> Self {
+        self.assert_same_library(other);
+        let grams = other.grams.iter().collect::<HashSet<_>>();
+        self.child_filtered(|lgram| !grams.contains(lgram))
+            .inherit(other)
+    }
+
+    /// Grams in exactly one of the librarians: those only in `self`, followed by those only in `other`.
+    ///
+    /// # Panics
+    /// If the librarians do not share a library.
+    #[must_use]
+    pub fn symmetric_difference(&self, other: &Self) -> Self {
+        self.assert_same_library(other);
+        let lhs = self.grams.iter().collect::<HashSet<_>>();
+        let rhs = other.grams.iter().collect::<HashSet<_>>();
+        let grams = self
+            .grams
+            .iter()
+            .filter(|lgram| !rhs.contains(lgram))
+            .chain(other.grams.iter().filter(|lgram| !lhs.contains(lgram)))
+            .cloned()
+            .collect();
+        self.child(grams).inherit(other)
+    }
+
+    /// Search with a tree of queries, see [QueryExpr](query::QueryExpr).
+    ///
+    /// Regex [matches](query::Match) are merged into a single search where that gives the same grams:
+    /// consecutive matches without repeats or a budget in an `And`, and matches of the same depth in an `Or`.
+    /// Matches merged in an `Or` share one budget, so they may stop sooner than they would apart.
+    /// A [Nearest](query::Nearest) query which finds nothing is empty rather than an error.
+    pub fn query(&self, expr: &query::QueryExpr<'_>) -> Result<Self> {
+        use query::QueryExpr;
+        match expr {
+            QueryExpr::Match(query) => self.search(query),
+            QueryExpr::Pattern(query) => self.pattern(query),
+            QueryExpr::Suffix(query) => self.suffix(query),
+            QueryExpr::Prefix(query) => self.complete(query),
+            QueryExpr::Anagram(query) => self.anagrams(query),
+            QueryExpr::Nearest(query) => match self.nearest(query) {
+                Ok((librarian, _)) => Ok(librarian),
+                Err(Error::NoNearest(_)) => Ok(self.child(Vec::new())),
+                Err(err) => Err(err),
+            },
+            QueryExpr::Distance(query) => self.distance(query),
+            QueryExpr::Has(query) => self.has(query),
+            QueryExpr::Only(query) => self.only(query),
+            QueryExpr::Letters(query) => self.letters(query),
+            QueryExpr::Wordle(query) => self.wordle(query),
+            QueryExpr::And(exprs) => self.query_and(exprs),
+            QueryExpr::Or(exprs) => self.query_or(exprs),
+            QueryExpr::Not(expr) => Ok(self.difference(&self.query(expr)?)),
+        }
+    }
+
+    /// Run each stage of a pipeline over the results of the last.
+    ///
+    /// Each query is given the budget, see [QueryExpr::budget](query::QueryExpr::budget).
+    pub fn run(&self, pipeline: &pipeline::Pipeline<'_>, budget: &query::Budget) -> Result<Self> {
+        let mut librarian = Cow::Borrowed(self);
+        for stage in &pipeline.stages {
+            librarian = Cow::Owned(match stage {
+                pipeline::Stage::Query(expr) => {
+                    librarian.query(&expr.clone().budget(budget.clone()))?
+                }
+                pipeline::Stage::Top(n) => librarian.top_seeds(*n),
+                pipeline::Stage::Count(n) => librarian.filter(|seed| seed.count >= *n),
+            });
+        }
+        Ok(librarian.into_owned())
+    }
+
+    /// The first `k` grams when ranked by the key, in ranked order.
+    ///
+    /// Only `k` grams are held while ranking, so this is cheaper than sorting them all.
+    #[must_use]
+    pub fn top_k(&self, k: usize, key: SortKey) -> Self {
+        if key == SortKey::Score && !self.is_annotated() {
+            return self.child(self.grams.iter().take(k).cloned().collect());
+        }
+
+        // The heap keeps the worst of the best `k` on top, ready to be displaced.
+        let mut heap = BinaryHeap::with_capacity(k.saturating_add(1).min(self.grams.len()));
+        for (position, lgram) in self.grams.iter().enumerate() {
+            heap.push(sort::Ranked {
+                key,
+                gram: lgram.as_gram(self.library),
+                note: self.note(lgram),
+                position,
+            });
+            if heap.len() > k {
+                heap.pop();
+            }
+        }
+        let grams = heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|ranked| self.grams[ranked.position].clone())
+            .collect();
+        self.child(grams)
+    }
+
+    /// Keep the grams whose seeds are all among the `n` most common in the librarian.
+    ///
+    /// Seeds with the same count are ranked by their order in the library,
+    /// so no more than `n` seeds are kept however many tie.
+    pub fn top_seeds(&self, n: usize) -> Self {
+        match self.top_seed_indices(n) {
+            Some(top) => self.filter(|seed| top.contains(&seed.index)),
+            None => self.clone(),
+        }
+    }
+
+    /// Keep the grams with none of their seeds among the `n` most common in the librarian,
+    /// ranked as by [top_seeds](Self::top_seeds).
+    pub fn without_top_seeds(&self, n: usize) -> Self {
+        match self.top_seed_indices(n) {
+            Some(top) => self.filter(|seed| !top.contains(&seed.index)),
+            None => self.filter(|_| false),
+        }
+    }
+
     pub fn stats(&self) -> Stats {
         self.into()
     }
+
+    /// Replace the search index with a minimised, immutable automaton.
+    ///
+    /// Grams sharing a suffix share its states, so the index uses far less memory than the
+    /// default trie. It is slower to build and cannot be narrowed by [filter](Self::filter),
+    /// so it suits read-only libraries which are searched many times.
+    ///
+    /// Librarians derived from this one, by a search or a filter, build a compact index of their
+    /// own grams when they are first searched.
+    #[must_use]
+    pub fn compact(self) -> Self {
+        let compact = Self {
+            index: OnceLock::new(),
+            compact: true,
+            ..self
+        };
+        compact.search_index();
+        compact
+    }
+}
+
+impl<'l> Librarian<'l> {
+    /// Create a librarian with a gram for each seed on the shelf.
+    pub fn new(library: &'l dyn Shelf) -> Self {
+        let grams = (0..library.len())
+            .map(|index| LibGram::Word(index, PhantomData))
+            .collect();
+        Self {
+            library,
+            grams,
+            index: OnceLock::new(),
+            compact: false,
+            trie_rev: OnceLock::new(),
+            truncated: None,
+            notes: Arc::default(),
+        }
+    }
 }
 
-impl<'l> From<&'l Library> for Librarian<'l> {
-    fn from(library: &'l Library) -> Self {
-        let grams = library.seeds.iter().map(LibGram::from).collect();
-        Self { library, grams }
+impl<'l, S: Shelf> From<&'l S> for Librarian<'l> {
+    fn from(library: &'l S) -> Self {
+        Self::new(library)
     }
 }
 
 #[must_use]
 pub struct Iter<'a, 'l: 'a> {
-    library: &'l Library,
+    library: &'l dyn Shelf,
     grams: std::slice::Iter<'a, LibGram<'l>>,
 }
 
@@ -262,7 +828,7 @@ impl<'a, 'l> IntoIterator for &'a Librarian<'l> {
 
 #[must_use]
 pub struct IntoIter<'l> {
-    library: &'l Library,
+    library: &'l dyn Shelf,
     grams: std::vec::IntoIter<LibGram<'l>>,
 }
 
@@ -304,105 +870,456 @@ impl<'l> Librarian<'l> {
         Self {
             library: self.library,
             grams,
+            index: OnceLock::new(),
+            compact: self.compact,
+            trie_rev: OnceLock::new(),
+            truncated: self.truncated,
+            notes: self.notes.clone(),
         }
     }
 
-    fn search_deep(&self, query: &query::Match<'_>) -> Result<Vec<LibGram<'l>>> {
-        let trie = Trie::from(self);
-        let dfa = regex_automata::dfa::dense::Builder::new().build(query.pattern)?;
-        self.search_trie(&trie, &dfa, query.depth)
+    /// Create a child of the grams found by a search, cut to the results of its budget.
+    fn child_budgeted(
+        &self,
+        mut grams: Vec<LibGram<'l>>,
+        mut limit: Option<query::Limit>,
+        budget: &query::Budget,
+    ) -> Self {
+        if let Some(max) = budget.results
+            && grams.len() > max
+        {
+            grams.truncate(max);
+            limit = limit.or(Some(query::Limit::Results));
+        }
+        let mut child = self.child(grams);
+        child.truncated = child.truncated.or(limit);
+        child
+    }
+
+    /// Create a child of the grams from a source, as far as the budget allows.
+    fn collect_source(&self, mut source: Box<dyn Source<'l> + '_>, budget: &query::Budget) -> Self {
+        let grams = source.by_ref().take(budget.results_taken()).collect();
+        self.child_budgeted(grams, source.limit(), budget)
+    }
+
+    /// Take on the truncation and annotations of `other`, whose grams were combined into these.
+    fn inherit(mut self, other: &Self) -> Self {
+        self.truncated = self.truncated.or(other.truncated);
+        if self.notes.is_empty() {
+            self.notes = other.notes.clone();
+        } else if !other.notes.is_empty() && !Arc::ptr_eq(&self.notes, &other.notes) {
+            let notes = Arc::make_mut(&mut self.notes);
+            for (lgram, note) in other.notes.iter() {
+                notes.entry(lgram.clone()).or_insert_with(|| note.clone());
+            }
+        }
+        self
+    }
+
+    /// Add to the annotations of the grams.
+    fn annotate(mut self, notes: impl IntoIterator<Item = (LibGram<'l>, Annotation)>) -> Self {
+        let mut notes = notes.into_iter().peekable();
+        if notes.peek().is_some() {
+            let map = Arc::make_mut(&mut self.notes);
+            for (lgram, note) in notes {
+                map.entry(lgram).or_default().merge(note);
+            }
+        }
+        self
+    }
+
+    /// Annotate each gram with the span matched by the first of the patterns which matches it,
+    /// as a [search](Self::search) for that pattern would.
+    fn annotate_spans(self, patterns: &[&str]) -> Result<Self> {
+        let regexes = patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let notes = self
+            .grams
+            .iter()
+            .filter_map(|lgram| {
+                let key = self.key(lgram);
+                let span = regexes.iter().find_map(|re| re.find(&key))?.range();
+                let note = Annotation {
+                    span: Some(span),
+                    ..Annotation::new()
+                };
+                Some((lgram.clone(), note))
+            })
+            .collect_vec();
+        Ok(self.annotate(notes))
+    }
+
+    /// The counts for the automaton of a [distinct](query::Letters::distinct) query, with each letter
+    /// used at most once.
+    ///
+    /// The automaton cannot count every letter, so the letters used most by the grams are added
+    /// to those of the query while there is room, and the rest are left to the histograms.
+    fn distinct_counts(
+        &self,
+        counts: &BTreeMap<char, RangeInclusive<usize>>,
+    ) -> BTreeMap<char, RangeInclusive<usize>> {
+        let mut counts = counts
+            .iter()
+            .map(|(&c, range)| (c, *range.start()..=(*range.end()).min(1)))
+            .collect::<BTreeMap<_, _>>();
+        // Each letter used at most once doubles the states of the automaton.
+        let room = (search::automata::LETTERS_STATES.ilog2() as usize)
+            .saturating_sub(counts.values().filter(|range| *range.end() == 1).count());
+
+        let mut uses = HashMap::<char, usize>::new();
+        for lgram in &self.grams {
+            for c in self.key(lgram).chars() {
+                *uses.entry(c).or_default() += 1;
+            }
+        }
+        let common = uses
+            .into_iter()
+            .filter(|(c, _)| !counts.contains_key(c))
+            .sorted_by_key(|&(c, uses)| (std::cmp::Reverse(uses), c))
+            .take(room)
+            .collect_vec();
+        counts.extend(common.into_iter().map(|(c, _)| (c, 0..=1)));
+        counts
+    }
+
+    /// Returns the indices of the `n` most common seeds, or `None` if there are no more than `n`.
+    fn top_seed_indices(&self, n: usize) -> Option<HashSet<usize>> {
+        let mut counts = self
+            .iter()
+            .flat_map(|gram| gram.seeds())
+            .map(|seed| (seed.index, seed.count))
+            .collect::<Vec<_>>();
+        counts.sort_unstable_by(|lhs, rhs| rhs.1.cmp(&lhs.1).then(lhs.0.cmp(&rhs.0)));
+        counts.dedup_by_key(|(index, _)| *index);
+        (counts.len() > n).then(|| counts.into_iter().take(n).map(|(index, _)| index).collect())
+    }
+
+    fn note(&self, lgram: &LibGram<'l>) -> &Annotation {
+        self.notes.get(lgram).unwrap_or(&UNANNOTATED)
+    }
+
+    /// Create a child containing the grams that `keep` accepts, in the same order.
+    ///
+    /// If the search tries have already been built, the child's tries are derived from them
+    /// rather than being rebuilt from scratch.
+    fn child_filtered(&self, mut keep: impl FnMut(&LibGram<'l>) -> bool) -> Self {
+        // Maps the index of each gram in this librarian to its index in the child.
+        let mut remap = Vec::with_capacity(self.grams.len());
+        let mut grams = Vec::new();
+        for lgram in self.grams.iter() {
+            if keep(lgram) {
+                remap.push(Some(grams.len()));
+                grams.push(lgram.clone());
+            } else {
+                remap.push(None);
+            }
+        }
+
+        let mut child = self.child(grams);
+        if child.grams.len() == self.grams.len() {
+            // Nothing was removed, so the indices are unchanged.
+            child.index = self.index.clone();
+            child.trie_rev = self.trie_rev.clone();
+        } else {
+            if let Some(SearchIndex::Trie(trie)) = self.index.get().map(Arc::as_ref) {
+                let trie = derive_trie(trie, &remap);
+                child.index = OnceLock::from(Arc::new(SearchIndex::Trie(trie)));
+            }
+            if let Some(trie) = self.trie_rev.get() {
+                child.trie_rev = OnceLock::from(Arc::new(derive_trie(trie, &remap)));
+            }
+        }
+        child
+    }
+
+    fn assert_same_library(&self, other: &Self) {
+        assert!(
+            std::ptr::addr_eq(self.library, other.library),
+            "Set operations require librarians of the same library"
+        );
+    }
+
+    /// Returns the indices of the seeds with the given roots, skipping any not in the library.
+    fn indices<'a>(&self, roots: impl IntoIterator<Item = &'a str>) -> HashSet<usize> {
+        roots
+            .into_iter()
+            .filter_map(|root| self.library.find(root))
+            .collect()
+    }
+
+    /// Returns the search index over the grams, building it on first use.
+    fn search_index(&self) -> &SearchIndex {
+        self.index.get_or_init(|| {
+            Arc::new(if self.compact {
+                SearchIndex::Dawg(
+                    self.grams
+                        .iter()
+                        .enumerate()
+                        .map(|(index, lgram)| (self.key(lgram).into_owned(), index))
+                        .collect(),
+                )
+            } else {
+                SearchIndex::Trie(Trie::from(self))
+            })
+        })
+    }
+
+    /// Returns the search trie over the reversed keys of the grams, building it on first use.
+    fn trie_rev(&self) -> &Trie<Vec<u8>, usize> {
+        self.trie_rev.get_or_init(|| {
+            Arc::new(
+                self.grams
+                    .iter()
+                    .enumerate()
+                    .map(|(index, lgram)| {
+                        let mut key = self.key(lgram).into_owned().into_bytes();
+                        key.reverse();
+                        (key, index)
+                    })
+                    .collect(),
+            )
+        })
+    }
+
+    /// Returns the trie key of a gram, the concatenation of its roots.
+    fn key(&self, lgram: &LibGram<'l>) -> Cow<'l, str> {
+        match lgram.as_gram(self.library) {
+            Gram::Word(seed) => Cow::Borrowed(seed.root),
+            Gram::Sequence(seeds) => Cow::Owned(seeds.into_iter().map(|seed| seed.root).join("")),
+        }
+    }
+
+    /// Search for the grams matching a regex pattern, lazily if `lazy` is set.
+    fn search_source(
+        &self,
+        query: &query::Match<'_>,
+        lazy: bool,
+    ) -> Result<Box<dyn Source<'l> + '_>> {
+        if query.depth > 0 {
+            let dfa = regex_automata::dfa::dense::Builder::new().build(query.pattern)?;
+            self.walk(dfa, query.depth, &query.budget, lazy)
+        } else {
+            let re = Regex::new(query.pattern)?;
+            #[cfg(feature = "parallel")]
+            if !lazy && splittable(&query.budget) {
+                let grams = parallel::filter(&self.grams, |lgram| re.is_match(&self.key(lgram)));
+                return Ok(Box::new(Found::new(grams, None)));
+            }
+            Ok(Box::new(Scan::new(
+                &self.grams,
+                &query.budget,
+                move |lgram| re.is_match(&self.key(lgram)),
+            )))
+        }
+    }
+
+    /// Walk the search index with the automaton, lazily unless the walk can be split across threads.
+    fn walk<'a>(
+        &'a self,
+        dfa: impl Automaton + Sync + 'a,
+        depth: usize,
+        budget: &query::Budget,
+        lazy: bool,
+    ) -> Result<Box<dyn Source<'l> + 'a>> {
+        if !lazy && splittable(budget) {
+            let (grams, limit) = self.search_trie(&dfa, depth, budget)?;
+            return Ok(Box::new(Found::new(grams, limit)));
+        }
+        Ok(match self.search_index() {
+            SearchIndex::Trie(trie) => Box::new(Walk::new(dfa, trie, depth, budget, &self.grams)?),
+            SearchIndex::Dawg(dawg) => {
+                Box::new(Walk::new(dfa, dawg.root(), depth, budget, &self.grams)?)
+            }
+        })
     }
 
     fn search_trie(
         &self,
-        trie: &Trie<String, &LibGram<'l>>,
-        dfa: &impl Automaton,
+        dfa: &(impl Automaton + Sync),
         depth: usize,
-    ) -> Result<Vec<LibGram<'l>>> {
-        let search = MultiHeadDFA::new(dfa, Nest::new(trie, depth))?;
+        budget: &query::Budget,
+    ) -> Result<Searched<LibGram<'l>>> {
+        let (lgrams, limit) = self.search_trie_state(dfa, depth, budget)?;
+        Ok((lgrams.into_iter().map(|(lgram, _)| lgram).collect(), limit))
+    }
 
-        Ok(search
-            .map(|(node, _)| {
+    fn search_trie_state(
+        &self,
+        dfa: &(impl Automaton + Sync),
+        depth: usize,
+        budget: &query::Budget,
+    ) -> Result<Searched<(LibGram<'l>, StateID)>> {
+        match self.search_index() {
+            SearchIndex::Trie(trie) => self.search_nodes(trie, dfa, depth, budget),
+            SearchIndex::Dawg(dawg) => self.search_nodes(dawg.root(), dfa, depth, budget),
+        }
+    }
+
+    /// Walk the nodes with the automaton until the search ends or spends its budget.
+    fn search_nodes<N: Node<u8> + Indexed + Debug + Send + Sync>(
+        &self,
+        root: N,
+        dfa: &(impl Automaton + Sync),
+        depth: usize,
+        budget: &query::Budget,
+    ) -> Result<Searched<(LibGram<'l>, StateID)>>
+    where
+        N::Children: Debug,
+    {
+        let found = |node: Nest<u8, N>, state_id: StateID| -> (LibGram<'l>, StateID) {
+            (
                 node.chain()
                     .into_iter()
-                    .map(|t| t.value.expect("Returned Nodes are leaves"))
-                    .collect()
-            })
-            .collect())
+                    .map(|t| &self.grams[t.index().expect("Returned Nodes are leaves")])
+                    .collect(),
+                state_id,
+            )
+        };
+
+        #[cfg(feature = "parallel")]
+        if splittable(budget) {
+            return Ok((parallel::walk(dfa, root, depth, found)?, None));
+        }
+
+        let mut search = MultiHeadDFA::new(dfa, Nest::new(root, depth))?.metered(budget.clone());
+        let lgrams = search
+            .by_ref()
+            .map(|(node, state_id)| found(node, state_id))
+            .collect();
+        Ok((lgrams, search.limit()))
     }
 
-    fn search_trie_state(
+    /// Deep search for grams matching any of the patterns in a single walk.
+    fn search_deep_set(
         &self,
-        trie: &Trie<String, &LibGram<'l>>,
-        dfa: &impl Automaton,
+        patterns: &[&str],
         depth: usize,
-    ) -> Result<Vec<(LibGram<'l>, StateID)>> {
-        let search = MultiHeadDFA::new(dfa, Nest::new(trie, depth))?;
+        budget: &query::Budget,
+    ) -> Result<Self> {
+        let dfa = regex_automata::dfa::dense::Builder::new().build_many(patterns)?;
+        let (grams, limit) = self.search_trie(&dfa, depth, budget)?;
+        Ok(self.child_budgeted(grams, limit, budget))
+    }
 
-        Ok(search
-            .map(|(node, state_id)| {
-                (
-                    node.chain()
-                        .into_iter()
-                        .map(|t| t.value.expect("Returned Nodes are leaves"))
-                        .collect(),
-                    state_id,
-                )
-            })
-            .collect())
+    fn query_and(&self, exprs: &[query::QueryExpr<'_>]) -> Result<Self> {
+        let mut librarian = Cow::Borrowed(self);
+        // Runs of flat matches filter without reordering, so each run is one pass.
+        for (flat, exprs) in &exprs.iter().chunk_by(|expr| flat_pattern(expr).is_some()) {
+            if flat {
+                let patterns = exprs.filter_map(flat_pattern).collect_vec();
+                let set = RegexSet::new(&patterns)?;
+                let child = librarian
+                    .child_filtered(|lgram| set.matches(&librarian.key(lgram)).matched_all());
+                // Each search would replace the span of the last, leaving that of the last pattern.
+                let last = &patterns[patterns.len() - 1..];
+                librarian = Cow::Owned(child.annotate_spans(last)?);
+            } else {
+                for expr in exprs {
+                    librarian = Cow::Owned(librarian.query(expr)?);
+                }
+            }
+        }
+        Ok(librarian.into_owned())
     }
 
-    fn search_flat(&self, query: &query::Match<'_>) -> Result<Vec<LibGram<'l>>> {
-        debug_assert_eq!(query.depth, 0, "Flat search does not support repeats");
-        let re = Regex::new(query.pattern)?;
-        Ok(self
-            .grams
-            .iter()
-            .filter_map(move |lgram| {
-                let word: String;
-                let text = match lgram {
-                    LibGram::Word(i, ..) => self.library.seeds[*i].root.as_str(),
-                    LibGram::Sequence(indices, ..) => {
-                        word = indices
-                            .iter()
-                            .map(|&i| &self.library.seeds[i].root)
-                            .join("");
-                        word.as_str()
-                    }
-                };
-                re.is_match(text).then(|| lgram.clone())
-            })
-            .collect())
+    fn query_or(&self, exprs: &[query::QueryExpr<'_>]) -> Result<Self> {
+        enum Part<'e, 'a> {
+            Expr(&'e query::QueryExpr<'a>),
+            /// Index of a group of matches with the same depth and budget.
+            Matches(usize),
+        }
+
+        let mut groups: Vec<(usize, &query::Budget, Vec<&str>)> = Vec::new();
+        let mut parts = Vec::new();
+        for expr in exprs {
+            let query::QueryExpr::Match(query) = expr else {
+                parts.push(Part::Expr(expr));
+                continue;
+            };
+            match groups
+                .iter_mut()
+                .find(|(depth, budget, _)| *depth == query.depth && **budget == query.budget)
+            {
+                Some((_, _, patterns)) => patterns.push(query.pattern),
+                None => {
+                    parts.push(Part::Matches(groups.len()));
+                    groups.push((query.depth, &query.budget, vec![query.pattern]));
+                }
+            }
+        }
+
+        let mut union: Option<Self> = None;
+        for part in parts {
+            let result = match part {
+                Part::Expr(expr) => self.query(expr)?,
+                Part::Matches(group) => {
+                    let (depth, budget, patterns) = &groups[group];
+                    let child = if *depth > 0 {
+                        self.search_deep_set(patterns, *depth, budget)?
+                    } else {
+                        let set = RegexSet::new(patterns)?;
+                        let keep = move |lgram: &LibGram<'l>| set.is_match(&self.key(lgram));
+                        if **budget == query::Budget::new() {
+                            self.child_filtered(keep)
+                        } else {
+                            self.collect_source(
+                                Box::new(Scan::new(&self.grams, budget, keep)),
+                                budget,
+                            )
+                        }
+                    };
+                    // A union keeps the annotation of the first search to find a gram.
+                    child.annotate_spans(patterns)?
+                }
+            };
+            union = Some(match union {
+                Some(union) => union.union(&result),
+                None => result,
+            });
+        }
+        Ok(union.unwrap_or_else(|| self.child(Vec::new())))
     }
+}
 
-    fn filter_seed(
-        &self,
-        mut f: impl FnMut(&'l Seed) -> bool,
-    ) -> impl Iterator<Item = LibGram<'l>> {
-        self.grams
-            .iter()
-            .filter(move |lgram| match lgram.as_gram(self.library) {
-                Gram::Word(seed) => f(seed),
-                Gram::Sequence(seeds) => seeds.iter().all(|&s| f(s)),
-            })
-            .cloned()
+/// Whether a search with the budget is split across threads, see the `parallel` feature.
+///
+/// A budgeted search stops at a point which depends on the order it is run in,
+/// so only an unlimited one is split.
+fn splittable(budget: &query::Budget) -> bool {
+    cfg!(feature = "parallel") && *budget == query::Budget::new()
+}
+
+/// The results of a search, and the limit which stopped it early if any.
+type Searched<T> = (Vec<T>, Option<query::Limit>);
+
+/// Returns the pattern of an unbudgeted match without repeats, which can be searched without a trie.
+fn flat_pattern<'a>(expr: &query::QueryExpr<'a>) -> Option<&'a str> {
+    match expr {
+        query::QueryExpr::Match(query)
+            if query.depth == 0 && query.budget == query::Budget::new() =>
+        {
+            Some(query.pattern)
+        }
+        _ => None,
     }
 }
 
-impl<'a, 'l> From<&'a Librarian<'l>> for Trie<String, &'a LibGram<'l>> {
+impl<'a, 'l> From<&'a Librarian<'l>> for Trie<String, usize> {
     fn from(librarian: &'a Librarian<'l>) -> Self {
         let mut trie = Trie::new();
-        for lgram in librarian.grams.iter() {
-            match lgram.as_gram(librarian.library) {
-                Gram::Word(seed) => {
-                    trie.insert(&seed.root, lgram);
-                }
-                Gram::Sequence(seeds) => {
-                    let key = seeds.into_iter().map(|seed| &seed.root).join("");
-                    trie.insert(&key, lgram);
-                }
-            }
+        for (index, lgram) in librarian.grams.iter().enumerate() {
+            trie.insert(librarian.key(lgram).as_ref(), index);
         }
         trie
     }
 }
+
+/// Derive the trie of a filtered child from its parent's.
+/// `remap` maps each index in the parent to its index in the child, if it was kept.
+fn derive_trie<K: Key + Clone>(trie: &Trie<K, usize>, remap: &[Option<usize>]) -> Trie<K, usize> {
+    let mut trie = trie.clone();
+    trie.retain(|_, index| remap[*index].map(|new| *index = new).is_some());
+    trie
+}
@@ -0,0 +1,64 @@
+//! Searches split across threads, enabled by the `parallel` feature.
+//!
+//! The work is split into chunks which are searched independently and joined back in order,
+//! so the results are the same as those of a search on one thread. See [splittable](super::splittable)
+//! for the searches which are split.
+use super::{
+    LibGram,
+    search::{MultiHeadDFA, Nest, Node},
+};
+use rayon::prelude::*;
+use regex_automata::{
+    dfa::{Automaton, StartError},
+    util::{primitives::StateID, start::Config},
+};
+use std::fmt::Debug;
+
+/// Keep the grams accepted, checking chunks of them on each thread.
+pub(super) fn filter<'l>(
+    grams: &[LibGram<'l>],
+    keep: impl Fn(&LibGram<'l>) -> bool + Sync,
+) -> Vec<LibGram<'l>> {
+    grams
+        .par_iter()
+        .filter(|lgram| keep(lgram))
+        .cloned()
+        .collect()
+}
+
+/// Walk the index with the automaton, searching each child of the root on its own thread.
+pub(super) fn walk<DFA, N, T>(
+    dfa: &DFA,
+    root: N,
+    depth: usize,
+    found: impl Fn(Nest<u8, N>, StateID) -> T + Sync,
+) -> Result<Vec<T>, StartError>
+where
+    DFA: Automaton + Sync,
+    N: Node<u8> + Debug + Send + Sync,
+    N::Children: Debug,
+    T: Send,
+{
+    if root.is_leaf() {
+        // A leaf at the root can nest into itself, so its children are not the only branches.
+        return Ok(MultiHeadDFA::new(dfa, Nest::new(root, depth))?
+            .map(|(node, state)| found(node, state))
+            .collect());
+    }
+
+    let start = dfa.start_state(&Config::new())?;
+    let branches = root.children().collect::<Vec<_>>();
+    let found = branches
+        .into_par_iter()
+        .map(|(byte, child)| {
+            let state = dfa.next_state(start, byte);
+            if dfa.is_dead_state(state) {
+                return Vec::new();
+            }
+            MultiHeadDFA::branch(dfa, Nest::branch(root.clone(), child, depth), state)
+                .map(|(node, state)| found(node, state))
+                .collect()
+        })
+        .collect::<Vec<Vec<_>>>();
+    Ok(found.into_iter().flatten().collect())
+}
@@ -0,0 +1,486 @@
+//! A small language for writing a search as a string, such as
+//! `anna pears -w1 | match '^s' | top 100`.
+//!
+//! A pipeline is a list of stages separated by `|`, each narrowing the results of the last.
+//! Arguments containing spaces or `|` are quoted with `'` or `"`, and there are no escapes.
+//!
+//! | Stage                                       | Query                                     |
+//! |---------------------------------------------|-------------------------------------------|
+//! | `match <pattern> [-d <depth>]`              | [Match](query::Match)                     |
+//! | `cross <pattern> [-d <depth>]`              | [Pattern](query::Pattern)                 |
+//! | `suffix <pattern> [-d <depth>]`             | [Suffix](query::Suffix)                   |
+//! | `complete <prefix>`                         | [Prefix](query::Prefix)                   |
+//! | `anna <letters> [-w <wild>] [-p] [-d <depth>]` | [Anagram](query::Anagram)              |
+//! | `fuzzy <pattern> [-e <edits>,..] [-m <max>]` | [Distance](query::Distance), or [Nearest](query::Nearest) without edits |
+//! | `has <letters>`                             | [Has](query::Has)                         |
+//! | `only <letters> [-r <required>] [-m <min>] [-d <depth>]` | [Only](query::Only)          |
+//! | `letters [-x <excluded>] [-u] [-c <count>,..] [-d <depth>]` | [Letters](query::Letters), see [Letters::parse_count](query::Letters::parse_count) |
+//! | `wordle <word:marks>..`                     | [Wordle](query::Wordle), see [Wordle::parse_guess](query::Wordle::parse_guess) |
+//! | `top <n>`                                   | Keep grams of the `n` most common seeds   |
+//! | `count <n>`                                 | Keep grams of seeds occurring at least `n` times |
+//!
+//! As on the command line, the depth is the number of grams joined together and defaults to 1.
+//! Flags take their value in the same word, `-w1`, or the next, `-w 1` and `--wild 1`.
+//! Any query stage can be negated with `not`, such as `not has e`.
+
+use crate::librarian::query;
+use std::ops::Range;
+use thiserror::Error;
+
+/// A parsed search, see the [module](self) documentation for the syntax.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Pipeline<'a> {
+    pub stages: Vec<Stage<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Stage<'a> {
+    /// Narrow to the grams matching a query.
+    Query(query::QueryExpr<'a>),
+    /// Keep the grams whose seeds are all among the `n` most common.
+    Top(usize),
+    /// Keep the grams whose seeds all occur at least `n` times.
+    Count(u64),
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{kind} at {}..{}", span.start, span.end)]
+pub struct ParseError {
+    /// Byte range of the input in error.
+    pub span: Range<usize>,
+    pub kind: ParseErrorKind,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    #[error("Empty stage")]
+    EmptyStage,
+    #[error("Unterminated quote")]
+    UnterminatedQuote,
+    #[error("Unknown command `{0}`")]
+    UnknownCommand(String),
+    #[error("Missing {0}")]
+    MissingArgument(&'static str),
+    #[error("Unexpected argument `{0}`")]
+    UnexpectedArgument(String),
+    #[error("Unknown flag `{0}`")]
+    UnknownFlag(String),
+    #[error("Missing value for flag `{0}`")]
+    MissingValue(String),
+    #[error("Flag `{0}` does not take a value")]
+    UnexpectedValue(String),
+    #[error("Invalid number `{0}`")]
+    InvalidNumber(String),
+    #[error("Invalid guess `{0}`, expected a word and its marks such as `crane:gy..g`")]
+    InvalidGuess(String),
+    #[error("Invalid count `{0}`, expected a letter and how many such as `s<=2`")]
+    InvalidCount(String),
+    #[error("`{0}` cannot be negated")]
+    NotNegatable(String),
+}
+
+impl<'a> Pipeline<'a> {
+    pub fn parse(input: &'a str) -> Result<Self, ParseError> {
+        let stages = tokenize(input)?
+            .into_iter()
+            .map(|tokens| stage(&tokens))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { stages })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token<'a> {
+    text: &'a str,
+    span: Range<usize>,
+    quoted: bool,
+}
+
+impl Token<'_> {
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            span: self.span.clone(),
+            kind,
+        }
+    }
+}
+
+/// Split the input into the tokens of each stage.
+fn tokenize(input: &str) -> Result<Vec<Vec<Token<'_>>>, ParseError> {
+    let mut stages = Vec::new();
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '|' => {
+                if tokens.is_empty() {
+                    return Err(ParseError {
+                        span: start..start + 1,
+                        kind: ParseErrorKind::EmptyStage,
+                    });
+                }
+                stages.push(std::mem::take(&mut tokens));
+            }
+            '\'' | '"' => {
+                let Some(len) = input[start + 1..].find(c) else {
+                    return Err(ParseError {
+                        span: start..input.len(),
+                        kind: ParseErrorKind::UnterminatedQuote,
+                    });
+                };
+                let end = start + 1 + len;
+                tokens.push(Token {
+                    text: &input[start + 1..end],
+                    span: start..end + 1,
+                    quoted: true,
+                });
+                while chars.next_if(|&(i, _)| i <= end).is_some() {}
+            }
+            _ => {
+                let mut end = input.len();
+                while let Some(&(i, c)) = chars.peek() {
+                    if c.is_whitespace() || c == '|' {
+                        end = i;
+                        break;
+                    }
+                    chars.next();
+                }
+                tokens.push(Token {
+                    text: &input[start..end],
+                    span: start..end,
+                    quoted: false,
+                });
+            }
+        }
+    }
+
+    if tokens.is_empty() {
+        if !stages.is_empty() {
+            return Err(ParseError {
+                span: input.len()..input.len(),
+                kind: ParseErrorKind::EmptyStage,
+            });
+        }
+    } else {
+        stages.push(tokens);
+    }
+    Ok(stages)
+}
+
+struct Flag {
+    short: char,
+    long: &'static str,
+    value: bool,
+}
+
+const DEPTH: Flag = Flag {
+    short: 'd',
+    long: "depth",
+    value: true,
+};
+
+/// The positional arguments and flags of a stage.
+struct Args<'a> {
+    command: Token<'a>,
+    positional: Vec<Token<'a>>,
+    flags: Vec<(&'static str, Option<Token<'a>>)>,
+}
+
+impl<'a> Args<'a> {
+    fn parse(command: Token<'a>, tokens: &[Token<'a>], spec: &[Flag]) -> Result<Self, ParseError> {
+        let mut positional = Vec::new();
+        let mut flags = Vec::new();
+        let mut tokens = tokens.iter();
+        while let Some(token) = tokens.next() {
+            let is_flag = !token.quoted
+                && token.text.len() > 1
+                && token.text.starts_with('-')
+                && token.text.parse::<f64>().is_err();
+            if !is_flag {
+                positional.push(token.clone());
+                continue;
+            }
+
+            let (flag, inline) = match token.text.strip_prefix("--") {
+                Some(long) => match long.split_once('=') {
+                    Some((long, value)) => (spec.iter().find(|f| f.long == long), Some(value)),
+                    None => (spec.iter().find(|f| f.long == long), None),
+                },
+                None => {
+                    let mut chars = token.text[1..].chars();
+                    let short = chars.next().expect("Flags are longer than `-`");
+                    let rest = chars.as_str();
+                    let flag = spec.iter().find(|f| f.short == short);
+                    (flag, (!rest.is_empty()).then_some(rest))
+                }
+            };
+            let Some(flag) = flag else {
+                return Err(token.error(ParseErrorKind::UnknownFlag(token.text.to_owned())));
+            };
+
+            let value = match (flag.value, inline) {
+                (false, None) => None,
+                (false, Some(_)) => {
+                    return Err(token.error(ParseErrorKind::UnexpectedValue(token.text.to_owned())));
+                }
+                // The value is the end of the flag's token.
+                (true, Some(value)) => Some(Token {
+                    text: value,
+                    span: token.span.end - value.len()..token.span.end,
+                    quoted: false,
+                }),
+                (true, None) => Some(tokens.next().cloned().ok_or_else(|| {
+                    token.error(ParseErrorKind::MissingValue(token.text.to_owned()))
+                })?),
+            };
+            flags.push((flag.long, value));
+        }
+        Ok(Self {
+            command,
+            positional,
+            flags,
+        })
+    }
+
+    /// The single positional argument of the stage.
+    fn positional(&self, name: &'static str) -> Result<&Token<'a>, ParseError> {
+        match self.positional.as_slice() {
+            [arg] => Ok(arg),
+            [] => Err(ParseError {
+                span: self.command.span.end..self.command.span.end,
+                kind: ParseErrorKind::MissingArgument(name),
+            }),
+            [_, extra, ..] => {
+                Err(extra.error(ParseErrorKind::UnexpectedArgument(extra.text.to_owned())))
+            }
+        }
+    }
+
+    /// Every positional argument of the stage, of which there must be at least one.
+    fn positionals(&self, name: &'static str) -> Result<&[Token<'a>], ParseError> {
+        if self.positional.is_empty() {
+            return Err(ParseError {
+                span: self.command.span.end..self.command.span.end,
+                kind: ParseErrorKind::MissingArgument(name),
+            });
+        }
+        Ok(&self.positional)
+    }
+
+    /// The value of the last use of a flag.
+    fn value(&self, long: &str) -> Option<&Token<'a>> {
+        self.flags
+            .iter()
+            .rev()
+            .find(|(name, _)| *name == long)
+            .and_then(|(_, value)| value.as_ref())
+    }
+
+    fn number<T: std::str::FromStr>(&self, long: &str) -> Result<Option<T>, ParseError> {
+        self.value(long).map(number).transpose()
+    }
+
+    fn is_set(&self, long: &str) -> bool {
+        self.flags.iter().any(|(name, _)| *name == long)
+    }
+
+    /// The depth flag as a library depth, counting from 0 rather than 1.
+    fn depth(&self) -> Result<usize, ParseError> {
+        match self.value(DEPTH.long) {
+            Some(token) => number::<usize>(token)?
+                .checked_sub(1)
+                .ok_or_else(|| token.error(ParseErrorKind::InvalidNumber(token.text.to_owned()))),
+            None => Ok(0),
+        }
+    }
+}
+
+fn number<T: std::str::FromStr>(token: &Token<'_>) -> Result<T, ParseError> {
+    token
+        .text
+        .parse()
+        .map_err(|_| token.error(ParseErrorKind::InvalidNumber(token.text.to_owned())))
+}
+
+/// Parse the tokens of a single stage.
+fn stage<'a>(tokens: &[Token<'a>]) -> Result<Stage<'a>, ParseError> {
+    let (negate, command, tokens) = match tokens {
+        [not, command, rest @ ..] if !not.quoted && not.text == "not" => (true, command, rest),
+        [not] if !not.quoted && not.text == "not" => {
+            return Err(ParseError {
+                span: not.span.end..not.span.end,
+                kind: ParseErrorKind::MissingArgument("command"),
+            });
+        }
+        [command, rest @ ..] => (false, command, rest),
+        [] => unreachable!("Stages are never empty"),
+    };
+    let args = |spec| Args::parse(command.clone(), tokens, spec);
+
+    let filter = match command.text {
+        "top" => Some(Stage::Top(number(args(&[])?.positional("number")?)?)),
+        "count" => Some(Stage::Count(number(args(&[])?.positional("number")?)?)),
+        _ => None,
+    };
+    if let Some(filter) = filter {
+        if negate {
+            return Err(command.error(ParseErrorKind::NotNegatable(command.text.to_owned())));
+        }
+        return Ok(filter);
+    }
+
+    let expr: query::QueryExpr = match command.text {
+        "match" => {
+            let args = args(&[DEPTH])?;
+            query::Match::new(args.positional("pattern")?.text)
+                .depth(args.depth()?)
+                .into()
+        }
+        "cross" => {
+            let args = args(&[DEPTH])?;
+            query::Pattern::new(args.positional("pattern")?.text)
+                .depth(args.depth()?)
+                .into()
+        }
+        "suffix" => {
+            let args = args(&[DEPTH])?;
+            query::Suffix::new(args.positional("pattern")?.text)
+                .depth(args.depth()?)
+                .into()
+        }
+        "complete" => {
+            let args = args(&[])?;
+            query::Prefix::new(args.positional("prefix")?.text).into()
+        }
+        "anna" => {
+            let args = args(&[
+                DEPTH,
+                Flag {
+                    short: 'w',
+                    long: "wild",
+                    value: true,
+                },
+                Flag {
+                    short: 'p',
+                    long: "partial",
+                    value: false,
+                },
+            ])?;
+            query::Anagram::new(args.positional("letters")?.text)
+                .wildcards(args.number("wild")?.unwrap_or(0))
+                .partial(args.is_set("partial"))
+                .depth(args.depth()?)
+                .into()
+        }
+        "fuzzy" => {
+            let args = args(&[
+                Flag {
+                    short: 'e',
+                    long: "edits",
+                    value: true,
+                },
+                Flag {
+                    short: 'm',
+                    long: "max",
+                    value: true,
+                },
+            ])?;
+            let pattern = args.positional("pattern")?.text;
+            match args.value("edits") {
+                Some(token) => {
+                    let edits = token
+                        .text
+                        .split(',')
+                        .map(|edit| {
+                            edit.parse().map_err(|_| {
+                                token.error(ParseErrorKind::InvalidNumber(edit.to_owned()))
+                            })
+                        })
+                        .collect::<Result<Vec<u8>, _>>()?;
+                    query::Distance::new(pattern, edits).strict(true).into()
+                }
+                None => {
+                    let max = args
+                        .number("max")?
+                        .unwrap_or(u8::try_from(pattern.len()).unwrap_or(u8::MAX));
+                    query::Nearest::new(pattern, max).into()
+                }
+            }
+        }
+        "has" => {
+            let args = args(&[])?;
+            query::Has::new(args.positional("letters")?.text).into()
+        }
+        "only" => {
+            let args = args(&[
+                DEPTH,
+                Flag {
+                    short: 'r',
+                    long: "required",
+                    value: true,
+                },
+                Flag {
+                    short: 'm',
+                    long: "min",
+                    value: true,
+                },
+            ])?;
+            query::Only::new(args.positional("letters")?.text)
+                .required(args.value("required").map_or("", |token| token.text))
+                .min_length(args.number("min")?.unwrap_or(0))
+                .depth(args.depth()?)
+                .into()
+        }
+        "letters" => {
+            let args = args(&[
+                DEPTH,
+                Flag {
+                    short: 'x',
+                    long: "exclude",
+                    value: true,
+                },
+                Flag {
+                    short: 'u',
+                    long: "unique",
+                    value: false,
+                },
+                Flag {
+                    short: 'c',
+                    long: "count",
+                    value: true,
+                },
+            ])?;
+            let mut query = query::Letters::new()
+                .exclude(args.value("exclude").map_or("", |token| token.text))
+                .distinct(args.is_set("unique"))
+                .depth(args.depth()?);
+            if let Some(token) = args.value("count") {
+                for count in token.text.split(',') {
+                    let (letter, range) = query::Letters::parse_count(count).ok_or_else(|| {
+                        token.error(ParseErrorKind::InvalidCount(count.to_owned()))
+                    })?;
+                    query = query.count(letter, range);
+                }
+            }
+            query.into()
+        }
+        "wordle" => {
+            let args = args(&[])?;
+            let mut query = query::Wordle::new();
+            for token in args.positionals("guess")? {
+                let (word, marks) = query::Wordle::parse_guess(token.text).ok_or_else(|| {
+                    token.error(ParseErrorKind::InvalidGuess(token.text.to_owned()))
+                })?;
+                query = query.guess(word, marks);
+            }
+            query.into()
+        }
+        _ => {
+            return Err(command.error(ParseErrorKind::UnknownCommand(command.text.to_owned())));
+        }
+    };
+
+    Ok(Stage::Query(if negate { !expr } else { expr }))
+}
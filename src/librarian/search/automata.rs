@@ -1,4 +1,4 @@
-use crate::librarian::Result;
+use crate::librarian::{Error, Result};
 use itertools::Itertools;
 use regex_automata::{
     PatternID,
@@ -6,8 +6,14 @@ use regex_automata::{
     nfa::thompson::{Builder, Transition},
     util::{look::Look, primitives::StateID},
 };
-use regex_syntax::utf8::Utf8Sequences;
-use std::collections::HashSet;
+use regex_syntax::{
+    hir::{ClassUnicode, ClassUnicodeRange},
+    utf8::Utf8Sequences,
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    ops::RangeInclusive,
+};
 
 /// Create the transitions for a set of UTF-8 sequences.
 /// Returns the start and end states of the transitions.
@@ -23,11 +29,13 @@ fn build_utf8_sequences(
             .into_iter()
             .rev()
             .try_fold(state_end, |next, range| {
-                builder.add_range(Transition {
-                    start: range.start,
-                    end: range.end,
-                    next,
-                })
+                builder
+                    .add_range(Transition {
+                        start: range.start,
+                        end: range.end,
+                        next,
+                    })
+                    .map_err(Error::from)
             })?;
         transitions.push(start);
     }
@@ -95,6 +103,7 @@ fn pattern_layer(
 /// despite the shortest distance being `0` for the full pattern.
 /// Therefore, if you want to find the nearest match, you should supply all distances up until the max distance.
 /// e.g. `0..=max_edits`
+#[allow(clippy::type_complexity)]
 pub fn levenshtein(
     pattern: &str,
     distances: impl IntoIterator<Item = u8>,
@@ -195,6 +204,150 @@ pub fn anagram(pattern: &str) -> Result<regex_automata::dfa::dense::DFA<Vec<u32>
     Ok(dfa)
 }
 
+/// Create a reverse DFA that matches any string ending with the regex `pattern`.
+/// The DFA must be fed the bytes of the string in reverse order.
+pub fn suffix(pattern: &str) -> Result<DFA<Vec<u32>>> {
+    let dfa = regex_automata::dfa::dense::Builder::new()
+        .thompson(regex_automata::nfa::thompson::Config::new().reverse(true))
+        .build(&format!("(?:{pattern})$"))?;
+    Ok(dfa)
+}
+
+/// Create a DFA that matches a crossword pattern, see [Pattern](super::query::Pattern).
+pub fn pattern(pattern: &str) -> Result<DFA<Vec<u32>>> {
+    let mut re = String::with_capacity(pattern.len() * 2 + 8);
+    re.push_str("^(?:");
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '?' => re.push('.'),
+            '*' => re.push_str(".*"),
+            '[' => {
+                let rest = chars.as_str();
+                let end = rest
+                    .find(']')
+                    .ok_or(Error::Pattern("unclosed letter class"))?;
+                let (negate, class) = match rest[..end].strip_prefix('^') {
+                    Some(class) => (true, class),
+                    None => (false, &rest[..end]),
+                };
+                if class.is_empty() {
+                    return Err(Error::Pattern("empty letter class"));
+                }
+
+                re.push_str(if negate { "[^" } else { "[" });
+                let last = class.chars().count() - 1;
+                for (i, c) in class.chars().enumerate() {
+                    // A dash between two letters is a range, elsewhere it is literal.
+                    if c == '-' && i != 0 && i != last {
+                        re.push('-');
+                    } else {
+                        re.push_str(&regex_syntax::escape(c.encode_utf8(&mut [0; 4])));
+                    }
+                }
+                re.push(']');
+                chars = rest[end + 1..].chars();
+            }
+            c => re.push_str(&regex_syntax::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    re.push_str(")$");
+
+    let dfa = regex_automata::dfa::dense::Builder::new().build(&re)?;
+    Ok(dfa)
+}
+
+/// Create a DFA that matches strings of at least `min_length` characters, using only those in `letters`.
+///
+/// A search index walked with it is pruned at the first character outside of the letters.
+pub fn only(letters: &str, min_length: usize) -> Result<DFA<Vec<u32>>> {
+    if letters.is_empty() {
+        return Ok(DFA::never_match()?);
+    }
+    let mut re = String::with_capacity(letters.len() * 2 + 16);
+    re.push_str("^[");
+    for c in letters.chars() {
+        re.push_str(&regex_syntax::escape(c.encode_utf8(&mut [0; 4])));
+    }
+    re.push_str(&format!("]{{{min_length},}}$"));
+
+    let dfa = regex_automata::dfa::dense::Builder::new().build(&re)?;
+    Ok(dfa)
+}
+
+/// Most states counting letters in the DFA built by [letters].
+pub const LETTERS_STATES: usize = 256;
+
+/// Create a DFA that matches strings using each letter no more than the end of its range.
+/// This produces a superset of the matches of a [Letters](super::query::Letters) query,
+/// as the fewest uses of a letter cannot narrow down a walk, and so are not counted.
+///
+/// Each state of the DFA holds how many times each letter has been used,
+/// so if they would need more than [LETTERS_STATES], the letters allowed the most uses are not counted.
+/// Letters which cannot be used at all are always left out.
+pub fn letters(counts: &BTreeMap<char, RangeInclusive<usize>>) -> Result<DFA<Vec<u32>>> {
+    let mut states = 1usize;
+    let counted = counts
+        .iter()
+        .map(|(&c, range)| (c, *range.end()))
+        .filter(|&(_, most)| most != 0 && most != usize::MAX)
+        .sorted_by_key(|&(_, most)| most)
+        .take_while(|&(_, most)| match states.checked_mul(most + 1) {
+            Some(next) if next <= LETTERS_STATES => {
+                states = next;
+                true
+            }
+            _ => false,
+        })
+        .collect_vec();
+    // Characters which are neither counted nor left out keep the count as it is.
+    let mut others = ClassUnicode::new(
+        counts
+            .iter()
+            .filter(|(_, range)| *range.end() == 0)
+            .map(|(&c, _)| c)
+            .chain(counted.iter().map(|&(c, _)| c))
+            .map(|c| ClassUnicodeRange::new(c, c)),
+    );
+    others.negate();
+
+    let mut builder = Builder::new();
+    builder.start_pattern()?;
+    let mut nodes = Vec::with_capacity(states);
+    for _ in 0..states {
+        nodes.push(builder.add_union(Vec::new())?);
+    }
+    let state_match = builder.add_match()?;
+    let state_end = builder.add_look(state_match, Look::End)?;
+
+    // The state of each count is its index, with a digit for each letter in the base of its most uses plus one.
+    for (index, &node) in nodes.iter().enumerate() {
+        builder.patch(node, state_end)?;
+        for range in others.iter() {
+            let (start, end) =
+                build_utf8_sequences(&mut builder, Utf8Sequences::new(range.start(), range.end()))?;
+            builder.patch(node, start)?;
+            builder.patch(end, node)?;
+        }
+
+        let mut stride = 1;
+        for &(c, most) in &counted {
+            if (index / stride) % (most + 1) < most {
+                let (start, end) = build_utf8_sequences(&mut builder, Utf8Sequences::new(c, c))?;
+                builder.patch(node, start)?;
+                builder.patch(end, nodes[index + stride])?;
+            }
+            stride *= most + 1;
+        }
+    }
+
+    let state_start = builder.add_look(nodes[0], Look::Start)?;
+    builder.finish_pattern(state_start)?;
+    let nfa = builder.build(state_start, state_start)?;
+    let dfa = regex_automata::dfa::dense::Builder::new().build_from_nfa(&nfa)?;
+    Ok(dfa)
+}
+
 /// Create a DFA to narrow down anagrams based on a pattern.
 /// This produces a superset of the anagram matches.
 ///
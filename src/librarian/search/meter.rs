@@ -0,0 +1,55 @@
+use super::query::{Budget, Limit};
+use std::time::Instant;
+
+/// Tracks the work done by a search against its [Budget].
+#[derive(Debug, Clone)]
+pub struct Meter {
+    budget: Budget,
+    nodes: usize,
+    limit: Option<Limit>,
+}
+
+impl Meter {
+    /// How many nodes are visited between checks of the clock and cancellation.
+    const CHECK_EVERY: usize = 1024;
+
+    pub fn new(budget: Budget) -> Self {
+        Self {
+            budget,
+            nodes: 0,
+            limit: None,
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(Budget::new())
+    }
+
+    /// Count a visited node, returning `false` once the budget is spent.
+    pub fn visit(&mut self) -> bool {
+        if self.limit.is_some() {
+            return false;
+        }
+        self.nodes += 1;
+        if self.budget.nodes.is_some_and(|max| self.nodes > max) {
+            self.limit = Some(Limit::Nodes);
+        } else if self.nodes % Self::CHECK_EVERY == 1 {
+            if self
+                .budget
+                .cancel
+                .as_ref()
+                .is_some_and(|c| c.is_cancelled())
+            {
+                self.limit = Some(Limit::Cancelled);
+            } else if self.budget.deadline.is_some_and(|d| Instant::now() >= d) {
+                self.limit = Some(Limit::Deadline);
+            }
+        }
+        self.limit.is_none()
+    }
+
+    /// The limit reached, if the budget is spent.
+    pub fn limit(&self) -> Option<Limit> {
+        self.limit
+    }
+}
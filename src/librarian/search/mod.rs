@@ -1,4 +1,6 @@
 pub mod automata;
+mod meter;
+pub use meter::Meter;
 mod multi_dfa;
 pub use multi_dfa::MultiHeadDFA;
 mod node;
@@ -10,3 +12,8 @@ pub trait Node<T>: Clone {
     fn children(&self) -> Self::Children;
     fn is_leaf(&self) -> bool;
 }
+
+/// A node which may refer to a gram by its index in a librarian.
+pub trait Indexed {
+    fn index(&self) -> Option<usize>;
+}
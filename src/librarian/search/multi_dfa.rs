@@ -1,4 +1,7 @@
-use super::Node;
+use super::{
+    Meter, Node,
+    query::{self, Budget},
+};
 use regex_automata::{
     dfa::Automaton,
     util::{primitives::StateID, start::Config},
@@ -47,12 +50,13 @@ impl<N: Node<u8>> Head<N> {
     }
 }
 
-pub struct MultiHeadDFA<'d, DFA: Automaton, N: Node<u8>> {
-    dfa: &'d DFA,
+pub struct MultiHeadDFA<DFA: Automaton, N: Node<u8>> {
+    dfa: DFA,
     heads: SmallVec<[Head<N>; 32]>,
+    meter: Meter,
 }
 
-impl<DFA: Automaton, N: Node<u8> + Debug> Debug for MultiHeadDFA<'_, DFA, N>
+impl<DFA: Automaton, N: Node<u8> + Debug> Debug for MultiHeadDFA<DFA, N>
 where
     N::Children: Debug,
 {
@@ -63,17 +67,44 @@ where
     }
 }
 
-impl<'d, DFA: Automaton, N: Node<u8>> MultiHeadDFA<'d, DFA, N> {
-    pub fn new(dfa: &'d DFA, node: N) -> Result<Self, regex_automata::dfa::StartError> {
+impl<DFA: Automaton, N: Node<u8>> MultiHeadDFA<DFA, N> {
+    pub fn new(dfa: DFA, node: N) -> Result<Self, regex_automata::dfa::StartError> {
         let first = Head::new(node, dfa.start_state(&Config::new())?);
         Ok(Self {
             dfa,
             heads: smallvec![first],
+            meter: Meter::unlimited(),
         })
     }
+
+    /// Start the search part way through the automaton, from a node reached in `state`.
+    #[cfg(feature = "parallel")]
+    pub fn branch(dfa: DFA, node: N, state: StateID) -> Self {
+        let head = if dfa.is_match_state(state) {
+            Head::accepting(node, state)
+        } else {
+            Head::new(node, state)
+        };
+        Self {
+            dfa,
+            heads: smallvec![head],
+            meter: Meter::unlimited(),
+        }
+    }
+
+    /// Stop the search once it has spent the budget.
+    pub fn metered(mut self, budget: Budget) -> Self {
+        self.meter = Meter::new(budget);
+        self
+    }
+
+    /// The limit which stopped the search, if it did not run to completion.
+    pub fn limit(&self) -> Option<query::Limit> {
+        self.meter.limit()
+    }
 }
 
-impl<DFA: Automaton, N: Node<u8>> Iterator for MultiHeadDFA<'_, DFA, N>
+impl<DFA: Automaton, N: Node<u8>> Iterator for MultiHeadDFA<DFA, N>
 where
     Self: Debug,
     N: Debug,
@@ -96,6 +127,10 @@ where
                     }
                     HeadPos::Children(ref mut children) => {
                         if let Some((byte, child)) = children.next() {
+                            if !self.meter.visit() {
+                                // Out of budget, the search ends here.
+                                break;
+                            }
                             let state = self.dfa.next_state(head.state, byte);
                             if self.dfa.is_dead_state(state) {
                                 continue;
@@ -123,13 +158,15 @@ where
                     }
                     HeadPos::Children(ref mut children) => {
                         if let Some((_, child)) = children.next() {
+                            if !self.meter.visit() {
+                                break;
+                            }
                             self.heads.push(Head::accepting(child, state));
                         } else {
                             self.heads.pop();
                         }
                     }
                 }
-                {}
             }
         }
         None
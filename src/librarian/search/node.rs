@@ -1,5 +1,6 @@
 use crate::{
-    librarian::search::Node,
+    dawg::{DawgNode, iter::Children},
+    librarian::search::{Indexed, Node},
     trie::{Key, Trie, iter::Bytes},
 };
 use std::rc::Rc;
@@ -15,6 +16,29 @@ impl<'a, K: Key + 'a, V: 'a> Node<u8> for &'a Trie<K, V> {
     }
 }
 
+impl<K: Key> Indexed for &Trie<K, usize> {
+    fn index(&self) -> Option<usize> {
+        self.value
+    }
+}
+
+impl<'a, V: 'a> Node<u8> for DawgNode<'a, V> {
+    type Children = Children<'a, V>;
+
+    fn children(&self) -> Self::Children {
+        DawgNode::children(self)
+    }
+    fn is_leaf(&self) -> bool {
+        self.is_final()
+    }
+}
+
+impl Indexed for DawgNode<'_, usize> {
+    fn index(&self) -> Option<usize> {
+        self.value().copied()
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct NestedNode<T, N: Node<T>> {
     root: Rc<N>,
@@ -51,6 +75,18 @@ impl<T, N: Node<T>> NestedNode<T, N> {
         }
     }
 
+    /// Creates a `NestedNode` at a child of the root, as found by the children of [new](Self::new).
+    #[cfg(feature = "parallel")]
+    pub fn branch(root: N, child: N, depth: usize) -> Self {
+        NestedNode {
+            root: Rc::new(root),
+            curr: child,
+            parent: None,
+            depth,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
     /// Returns an iterator of the nodes from the current node to the root.
     pub fn chain_rev(&self) -> impl Iterator<Item = &N> {
         let mut current = Some(self);
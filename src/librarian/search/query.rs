@@ -14,12 +14,26 @@
 //! (the `d+1`-[fold Cartesian product](https://en.wikipedia.org/wiki/Cartesian_product#Cartesian_products_of_several_sets) of `S` with itself).
 //!
 //! A depth of 0 is the default.
+//!
+//! ## Budget
+//! Deep searches can visit a vast number of grams, so they may be given a [Budget].
+
+use std::{
+    collections::BTreeMap,
+    ops::{Bound, RangeBounds, RangeInclusive},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
 /// A query that matches a regex pattern.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Match<'a> {
     pub(in crate::librarian) pattern: &'a str,
     pub(in crate::librarian) depth: usize,
+    pub(in crate::librarian) budget: Budget,
 }
 
 impl<'a> From<&'a str> for Match<'a> {
@@ -30,7 +44,54 @@ impl<'a> From<&'a str> for Match<'a> {
 
 impl<'a> Match<'a> {
     pub fn new(pattern: &'a str) -> Self {
-        Self { pattern, depth: 0 }
+        Self {
+            pattern,
+            depth: 0,
+            budget: Budget::new(),
+        }
+    }
+
+    /// Set the depth of the search. See the [module](self) documentation for details.
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Limit the work done by the search, see [Budget].
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+}
+
+/// A query that matches a crossword pattern against the whole of each gram.
+///
+/// A `?` is any one character, `*` is any run of characters, including none, and
+/// `[aeiou]` is any one of the letters listed, or any but them as `[^aeiou]`.
+/// Every other character matches itself, so `c?t` finds "cat" and "cot".
+///
+/// The pattern is matched against the roots of a sequence joined without spaces,
+/// so at a depth of 1, `?????????` finds pairs of words with 9 letters between them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Pattern<'a> {
+    pub(in crate::librarian) pattern: &'a str,
+    pub(in crate::librarian) depth: usize,
+    pub(in crate::librarian) budget: Budget,
+}
+
+impl<'a> From<&'a str> for Pattern<'a> {
+    fn from(pattern: &'a str) -> Self {
+        Self::new(pattern)
+    }
+}
+
+impl<'a> Pattern<'a> {
+    pub fn new(pattern: &'a str) -> Self {
+        Self {
+            pattern,
+            depth: 0,
+            budget: Budget::new(),
+        }
     }
 
     /// Set the depth of the search. See the [module](self) documentation for details.
@@ -38,6 +99,196 @@ impl<'a> Match<'a> {
         self.depth = depth;
         self
     }
+
+    /// Limit the work done by the search, see [Budget].
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+}
+
+/// The colour of a letter in the feedback on a [Wordle] guess.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mark {
+    /// The letter is in this position of the answer.
+    Green,
+    /// The letter is in the answer, but not in this position.
+    Yellow,
+    /// The letter is not in the answer, beyond those marked green or yellow.
+    Grey,
+}
+
+impl Mark {
+    /// Read a mark from `g` for green, `y` for yellow, or any of `.`, `-`, `x` and `b` for grey.
+    #[must_use]
+    pub fn from_char(c: char) -> Option<Self> {
+        match c.to_ascii_lowercase() {
+            'g' => Some(Self::Green),
+            'y' => Some(Self::Yellow),
+            '.' | '-' | 'x' | 'b' => Some(Self::Grey),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn to_char(self) -> char {
+        match self {
+            Self::Green => 'g',
+            Self::Yellow => 'y',
+            Self::Grey => '.',
+        }
+    }
+}
+
+/// Find the answers left by the feedback on guesses in a game of Wordle.
+///
+/// Each guess is marked letter by letter, and the answer has as many letters as the guesses.
+/// A letter marked grey as well as green or yellow pins how many times it is in the answer,
+/// so `speed` marked `..gy.` means there are exactly two `e`s.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Wordle<'a> {
+    pub(in crate::librarian) guesses: Vec<(&'a str, Vec<Mark>)>,
+    pub(in crate::librarian) budget: Budget,
+}
+
+impl<'a> Wordle<'a> {
+    /// Create a query without guesses, which finds every gram.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a guess and the mark given to each of its letters.
+    pub fn guess(mut self, word: &'a str, marks: impl IntoIterator<Item = Mark>) -> Self {
+        self.guesses.push((word, marks.into_iter().collect()));
+        self
+    }
+
+    /// Limit the work done by the search, see [Budget].
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Read a guess written as `word:marks`, such as `crane:gy..g`, see [Mark::from_char].
+    #[must_use]
+    pub fn parse_guess(guess: &str) -> Option<(&str, Vec<Mark>)> {
+        let (word, marks) = guess.split_once(':')?;
+        let marks = marks.chars().map(Mark::from_char).collect::<Option<_>>()?;
+        Some((word, marks))
+    }
+}
+
+/// How a guess is scored when [ranking guesses](Guesses).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Strategy {
+    /// Expected information gained from the marks, in bits, most first.
+    #[default]
+    Information,
+    /// Expected number of candidates left after the marks, fewest first.
+    /// None are left when the guess is the answer.
+    Remaining,
+}
+
+/// Rank guesses in a game of Wordle by how far their marks would narrow down the answer,
+/// see [Librarian::rank_guesses](crate::librarian::Librarian::rank_guesses).
+///
+/// The grams of the librarian are the candidates for the answer, such as those left by a [Wordle] query.
+/// By default, each candidate is as likely as the others and only they are ranked as guesses.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Guesses {
+    pub(in crate::librarian) strategy: Strategy,
+    pub(in crate::librarian) weighted: bool,
+    pub(in crate::librarian) any: bool,
+    pub(in crate::librarian) top: usize,
+    pub(in crate::librarian) budget: Budget,
+}
+
+impl Default for Guesses {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Guesses {
+    /// Rank the 10 guesses which gain the most information.
+    pub fn new() -> Self {
+        Self {
+            strategy: Strategy::Information,
+            weighted: false,
+            any: false,
+            top: 10,
+            budget: Budget::new(),
+        }
+    }
+
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Weight each candidate by its count, so common words are thought more likely to be the answer.
+    pub fn weighted(mut self, weighted: bool) -> Self {
+        self.weighted = weighted;
+        self
+    }
+
+    /// Also rank the seeds of the library which are not candidates, but are as long as one.
+    /// These can narrow down the answer further, but cannot be it.
+    pub fn any(mut self, any: bool) -> Self {
+        self.any = any;
+        self
+    }
+
+    /// Number of guesses to return.
+    pub fn top(mut self, top: usize) -> Self {
+        self.top = top;
+        self
+    }
+
+    /// Limit the work done by the ranking, see [Budget].
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+}
+
+/// A query that matches a regex pattern against the end of each gram.
+///
+/// Unlike [Match], the search walks the grams from their last character,
+/// so only grams sharing the suffix are visited.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Suffix<'a> {
+    pub(in crate::librarian) pattern: &'a str,
+    pub(in crate::librarian) depth: usize,
+    pub(in crate::librarian) budget: Budget,
+}
+
+impl<'a> From<&'a str> for Suffix<'a> {
+    fn from(pattern: &'a str) -> Self {
+        Self::new(pattern)
+    }
+}
+
+impl<'a> Suffix<'a> {
+    pub fn new(pattern: &'a str) -> Self {
+        Self {
+            pattern,
+            depth: 0,
+            budget: Budget::new(),
+        }
+    }
+
+    /// Set the depth of the search. See the [module](self) documentation for details.
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Limit the work done by the search, see [Budget].
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
 }
 
 /// Search for anagrams given a pattern of characters.
@@ -51,6 +302,7 @@ pub struct Anagram<'a> {
     pub(in crate::librarian) wildcards: usize,
     pub(in crate::librarian) depth: usize,
     pub(in crate::librarian) partial: bool,
+    pub(in crate::librarian) budget: Budget,
 }
 
 impl<'a> From<&'a str> for Anagram<'a> {
@@ -77,6 +329,7 @@ impl<'a> Anagram<'a> {
             wildcards: 0,
             depth: 0,
             partial: false,
+            budget: Budget::new(),
         }
     }
 
@@ -100,17 +353,34 @@ impl<'a> Anagram<'a> {
         self.partial = partial;
         self
     }
+
+    /// Limit the work done by the search, see [Budget].
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Nearest<'a> {
     pub(in crate::librarian) pattern: &'a str,
     pub(in crate::librarian) distance: u8,
+    pub(in crate::librarian) budget: Budget,
 }
 
 impl<'a> Nearest<'a> {
     pub fn new(pattern: &'a str, distance: u8) -> Self {
-        Self { pattern, distance }
+        Self {
+            pattern,
+            distance,
+            budget: Budget::new(),
+        }
+    }
+
+    /// Limit the work done by the search, see [Budget].
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
     }
 }
 
@@ -119,6 +389,7 @@ pub struct Distance<'a> {
     pub(in crate::librarian) pattern: &'a str,
     pub(in crate::librarian) distances: Vec<u8>,
     pub(in crate::librarian) strict: bool,
+    pub(in crate::librarian) budget: Budget,
 }
 
 impl<'a> Distance<'a> {
@@ -127,6 +398,7 @@ impl<'a> Distance<'a> {
             pattern,
             distances: distances.into_iter().collect(),
             strict: false,
+            budget: Budget::new(),
         }
     }
 
@@ -134,6 +406,12 @@ impl<'a> Distance<'a> {
         self.strict = strict;
         self
     }
+
+    /// Limit the work done by the search, see [Budget].
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -152,3 +430,470 @@ impl<'a> Has<'a> {
         Self { characters }
     }
 }
+
+/// Find grams made only of letters from a bank, such as in a Spelling Bee.
+///
+/// Each letter of the bank may be used any number of times, and the [required](Self::required)
+/// letters must each be used at least once.
+/// Every gram found is given a score: 1 point at the [minimum length](Self::min_length) if one is set,
+/// otherwise a point per letter, with a bonus of a point per letter of the bank for a pangram using all of them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Only<'a> {
+    pub(in crate::librarian) letters: &'a str,
+    pub(in crate::librarian) required: &'a str,
+    pub(in crate::librarian) min_length: usize,
+    pub(in crate::librarian) depth: usize,
+    pub(in crate::librarian) budget: Budget,
+}
+
+impl<'a> From<&'a str> for Only<'a> {
+    fn from(letters: &'a str) -> Self {
+        Self::new(letters)
+    }
+}
+
+impl<'a> Only<'a> {
+    pub fn new(letters: &'a str) -> Self {
+        Self {
+            letters,
+            required: "",
+            min_length: 0,
+            depth: 0,
+            budget: Budget::new(),
+        }
+    }
+
+    /// Letters which must each be used at least once.
+    pub fn required(mut self, required: &'a str) -> Self {
+        self.required = required;
+        self
+    }
+
+    /// Fewest letters in a gram, counted over every word of a sequence.
+    pub fn min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    /// Set the depth of the search. See the [module](self) documentation for details.
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Limit the work done by the search, see [Budget].
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+}
+
+/// Find grams by how many times each letter is used in them.
+///
+/// Such as lipograms without an `e`, `Letters::new().exclude("e")`,
+/// isograms without a repeated letter, `Letters::new().distinct(true)`,
+/// or those with at most two `s`, `Letters::new().count('s', ..=2)`.
+///
+/// The letters of a sequence are counted over all of its words.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Letters {
+    pub(in crate::librarian) counts: BTreeMap<char, RangeInclusive<usize>>,
+    pub(in crate::librarian) distinct: bool,
+    pub(in crate::librarian) depth: usize,
+    pub(in crate::librarian) budget: Budget,
+}
+
+impl Letters {
+    /// Create a query without constraints, which finds every gram.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how many times a letter may be used, replacing any range set for it before.
+    pub fn count(mut self, letter: char, range: impl RangeBounds<usize>) -> Self {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => Some(end),
+            Bound::Excluded(&end) => end.checked_sub(1),
+            Bound::Unbounded => Some(usize::MAX),
+        };
+        // An empty range, such as `..0`, finds nothing.
+        self.counts.insert(
+            letter,
+            end.map_or(RangeInclusive::new(1, 0), |end| start..=end),
+        );
+        self
+    }
+
+    /// Use none of these letters.
+    pub fn exclude(self, letters: &str) -> Self {
+        letters
+            .chars()
+            .fold(self, |letters, letter| letters.count(letter, 0..=0))
+    }
+
+    /// Use each letter at most once.
+    ///
+    /// The search can only count a few letters at once, so it skips grams repeating the letters
+    /// used most in the librarian, and the rest are checked against each gram found.
+    pub fn distinct(mut self, distinct: bool) -> Self {
+        self.distinct = distinct;
+        self
+    }
+
+    /// Set the depth of the search. See the [module](self) documentation for details.
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Limit the work done by the search, see [Budget].
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Read a count of a letter written as `s<=2`, `e>=1` or `a=2`.
+    #[must_use]
+    pub fn parse_count(count: &str) -> Option<(char, RangeInclusive<usize>)> {
+        let mut chars = count.chars();
+        let letter = chars.next()?;
+        let rest = chars.as_str();
+        let (range, number): (fn(usize) -> RangeInclusive<usize>, _) =
+            if let Some(number) = rest.strip_prefix("<=") {
+                (|n| 0..=n, number)
+            } else if let Some(number) = rest.strip_prefix(">=") {
+                (|n| n..=usize::MAX, number)
+            } else {
+                (|n| n..=n, rest.strip_prefix('=')?)
+            };
+        Some((letter, range(number.parse().ok()?)))
+    }
+}
+
+/// Select the grams within a lexicographic range, returned in that order.
+///
+/// Sequences are compared by the concatenation of their roots.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Range<'a> {
+    pub(in crate::librarian) start: Bound<&'a str>,
+    pub(in crate::librarian) end: Bound<&'a str>,
+    pub(in crate::librarian) reverse: bool,
+}
+
+impl<'a> Range<'a> {
+    /// Create a new range query, such as `Range::new("cat".."cow")`.
+    /// An unbounded range, `Range::new(..)`, selects every gram in sorted order.
+    pub fn new(range: impl RangeBounds<&'a str>) -> Self {
+        Self {
+            start: range.start_bound().cloned(),
+            end: range.end_bound().cloned(),
+            reverse: false,
+        }
+    }
+
+    /// Return the grams in descending order.
+    pub fn reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+}
+
+/// Complete a prefix to every gram that starts with it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Prefix<'a> {
+    pub(in crate::librarian) pattern: &'a str,
+    pub(in crate::librarian) budget: Budget,
+}
+
+impl<'a> From<&'a str> for Prefix<'a> {
+    fn from(pattern: &'a str) -> Self {
+        Self::new(pattern)
+    }
+}
+
+impl<'a> Prefix<'a> {
+    pub fn new(pattern: &'a str) -> Self {
+        Self {
+            pattern,
+            budget: Budget::new(),
+        }
+    }
+
+    /// Limit the work done by the search, see [Budget].
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+}
+
+/// Limits on the work done by a search. By default, a search is unlimited.
+///
+/// A search which runs out of budget returns the grams found so far, and the
+/// [Limit] reached is reported by [Librarian::truncated](crate::librarian::Librarian::truncated).
+///
+/// Most searches count each node of the search index they visit. A [Prefix] search counts
+/// each gram starting with the prefix, and keeps the most common of those it visited.
+/// Ranking [Guesses] counts each guess scored, and ranks only those scored before the budget
+/// is spent, while the number of guesses returned is set by [top](Guesses::top) alone.
+/// A [Has] query scans each gram once, so it has no budget.
+///
+/// Some searches narrow the grams they walk to before returning them: [Wordle], [Nearest],
+/// [Distance], [Prefix], [Only], [Letters], and [Anagram] with wildcards or a long pattern.
+/// Capping the walk would cut grams that might have been kept, so there only the grams returned
+/// are capped by [results](Self::results), and the walk stops early only for
+/// [nodes](Self::nodes), the [deadline](Self::deadline) or [cancel](Self::cancel).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Budget {
+    pub(in crate::librarian) results: Option<usize>,
+    pub(in crate::librarian) nodes: Option<usize>,
+    pub(in crate::librarian) deadline: Option<Instant>,
+    pub(in crate::librarian) cancel: Option<CancelToken>,
+}
+
+impl Budget {
+    pub const fn new() -> Self {
+        Self {
+            results: None,
+            nodes: None,
+            deadline: None,
+            cancel: None,
+        }
+    }
+
+    /// Stop once this many grams have been found.
+    pub fn results(mut self, results: usize) -> Self {
+        self.results = Some(results);
+        self
+    }
+
+    /// Stop once this many nodes of the search index have been visited.
+    pub fn nodes(mut self, nodes: usize) -> Self {
+        self.nodes = Some(nodes);
+        self
+    }
+
+    /// Stop once this instant has passed.
+    ///
+    /// The clock is unavailable on `wasm32-unknown-unknown`, use [nodes](Self::nodes) there instead.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Stop once this much time has passed from now, see [deadline](Self::deadline).
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.deadline(Instant::now() + timeout)
+    }
+
+    /// Stop once the token is cancelled.
+    pub fn cancel(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// This budget for a walk whose grams are narrowed further, see [Budget].
+    pub(in crate::librarian) fn without_results(&self) -> Self {
+        Self {
+            results: None,
+            ..self.clone()
+        }
+    }
+
+    /// The number of grams to take from a search.
+    /// One gram past the results is taken, so a search stopped by them can be told apart.
+    pub(in crate::librarian) fn results_taken(&self) -> usize {
+        self.results.map_or(usize::MAX, |max| max.saturating_add(1))
+    }
+}
+
+/// Cancels the searches given a clone of it, from any thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl PartialEq for CancelToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for CancelToken {}
+
+impl std::hash::Hash for CancelToken {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state);
+    }
+}
+
+/// The part of a [Budget] which stopped a search early.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limit {
+    Results,
+    Nodes,
+    Deadline,
+    Cancelled,
+}
+
+impl Limit {
+    /// A stable name for the limit: `results`, `nodes`, `deadline` or `cancelled`.
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Limit::Results => "results",
+            Limit::Nodes => "nodes",
+            Limit::Deadline => "deadline",
+            Limit::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Written as its [name](Limit::as_str).
+impl std::fmt::Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A tree of queries combined with boolean operators, see [Librarian::query](crate::librarian::Librarian::query).
+///
+/// Build one from any query with [From], and combine them with `&`, `|` and `!`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum QueryExpr<'a> {
+    Match(Match<'a>),
+    Pattern(Pattern<'a>),
+    Suffix(Suffix<'a>),
+    Prefix(Prefix<'a>),
+    Anagram(Anagram<'a>),
+    Nearest(Nearest<'a>),
+    Distance(Distance<'a>),
+    Has(Has<'a>),
+    Only(Only<'a>),
+    Letters(Letters),
+    Wordle(Wordle<'a>),
+    /// Grams matching every query.
+    /// Each query is searched over the results of the one before it.
+    And(Vec<QueryExpr<'a>>),
+    /// Grams matching any query, in the order they are found.
+    Or(Vec<QueryExpr<'a>>),
+    /// Grams not matching the query.
+    Not(Box<QueryExpr<'a>>),
+}
+
+macro_rules! impl_from_query {
+    ($($query:ident),*) => {
+        $(
+            impl<'a> From<$query<'a>> for QueryExpr<'a> {
+                fn from(query: $query<'a>) -> Self {
+                    Self::$query(query)
+                }
+            }
+        )*
+    };
+}
+impl_from_query!(
+    Match, Pattern, Suffix, Prefix, Anagram, Nearest, Distance, Has, Only, Wordle
+);
+
+impl From<Letters> for QueryExpr<'_> {
+    fn from(query: Letters) -> Self {
+        Self::Letters(query)
+    }
+}
+
+impl QueryExpr<'_> {
+    /// Limit the work done by each query in the tree, replacing any budget it had, see [Budget].
+    pub fn budget(self, budget: Budget) -> Self {
+        match self {
+            Self::Match(query) => Self::Match(query.budget(budget)),
+            Self::Pattern(query) => Self::Pattern(query.budget(budget)),
+            Self::Suffix(query) => Self::Suffix(query.budget(budget)),
+            Self::Prefix(query) => Self::Prefix(query.budget(budget)),
+            Self::Anagram(query) => Self::Anagram(query.budget(budget)),
+            Self::Nearest(query) => Self::Nearest(query.budget(budget)),
+            Self::Distance(query) => Self::Distance(query.budget(budget)),
+            Self::Has(query) => Self::Has(query),
+            Self::Only(query) => Self::Only(query.budget(budget)),
+            Self::Letters(query) => Self::Letters(query.budget(budget)),
+            Self::Wordle(query) => Self::Wordle(query.budget(budget)),
+            Self::And(exprs) => Self::And(
+                exprs
+                    .into_iter()
+                    .map(|expr| expr.budget(budget.clone()))
+                    .collect(),
+            ),
+            Self::Or(exprs) => Self::Or(
+                exprs
+                    .into_iter()
+                    .map(|expr| expr.budget(budget.clone()))
+                    .collect(),
+            ),
+            Self::Not(expr) => Self::Not(Box::new(expr.budget(budget))),
+        }
+    }
+}
+
+impl<'a, T: Into<QueryExpr<'a>>> std::ops::BitAnd<T> for QueryExpr<'a> {
+    type Output = Self;
+
+    fn bitand(self, rhs: T) -> Self {
+        match (self, rhs.into()) {
+            (Self::And(mut lhs), Self::And(rhs)) => {
+                lhs.extend(rhs);
+                Self::And(lhs)
+            }
+            (Self::And(mut lhs), rhs) => {
+                lhs.push(rhs);
+                Self::And(lhs)
+            }
+            (lhs, rhs) => Self::And(vec![lhs, rhs]),
+        }
+    }
+}
+
+impl<'a, T: Into<QueryExpr<'a>>> std::ops::BitOr<T> for QueryExpr<'a> {
+    type Output = Self;
+
+    fn bitor(self, rhs: T) -> Self {
+        match (self, rhs.into()) {
+            (Self::Or(mut lhs), Self::Or(rhs)) => {
+                lhs.extend(rhs);
+                Self::Or(lhs)
+            }
+            (Self::Or(mut lhs), rhs) => {
+                lhs.push(rhs);
+                Self::Or(lhs)
+            }
+            (lhs, rhs) => Self::Or(vec![lhs, rhs]),
+        }
+    }
+}
+
+impl std::ops::Not for QueryExpr<'_> {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        match self {
+            Self::Not(expr) => *expr,
+            expr => Self::Not(Box::new(expr)),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::librarian::{Metadata, Seed};
+use std::{
+    borrow::Cow,
+    fmt::Debug,
+    ops::{Bound, Range, RangeBounds},
+};
+
+/// Storage of the seeds a [Librarian](super::Librarian) searches.
+///
+/// Implemented by an owned [Library](super::Library), a borrowed [Section] of another shelf
+/// and a [BinaryLibrary](super::binary::BinaryLibrary) read in place, such as from a memory map.
+pub trait Shelf: Debug + Send + Sync {
+    /// Number of seeds on the shelf.
+    fn len(&self) -> usize;
+
+    /// Returns the seed at this index.
+    ///
+    /// # Panics
+    /// If the index is out of bounds.
+    fn seed(&self, index: usize) -> Seed<'_>;
+
+    /// Returns the index of the seed with this root.
+    /// If several seeds share the root, this is the first of them.
+    fn find(&self, root: &str) -> Option<usize>;
+
+    /// Returns the index of the first seed with this root within the range of indices.
+    ///
+    /// Only a shelf with a seed of this root before the range is scanned for a later one.
+    fn find_in(&self, root: &str, range: Range<usize>) -> Option<usize> {
+        match self.find(root)? {
+            index if index >= range.start => (index < range.end).then_some(index),
+            _ => range
+                .into_iter()
+                .find(|&index| self.seed(index).root == root),
+        }
+    }
+
+    /// Returns `true` if there are no seeds on the shelf.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the seed associated with this root.
+    fn get(&self, root: &str) -> Option<Seed<'_>> {
+        self.find(root).map(|index| self.seed(index))
+    }
+
+    /// Returns `true` if the shelf has a seed with this root.
+    fn contains(&self, root: &str) -> bool {
+        self.find(root).is_some()
+    }
+
+    /// Details of how the seeds were built, empty if none were recorded.
+    fn metadata(&self) -> Cow<'_, Metadata> {
+        Cow::Owned(Metadata::default())
+    }
+}
+
+/// A borrowed view of a contiguous range of seeds on another shelf.
+///
+/// Seeds are indexed from the start of the range.
+#[derive(Debug, Clone, Copy)]
+pub struct Section<'a> {
+    shelf: &'a dyn Shelf,
+    start: usize,
+    end: usize,
+}
+
+impl<'a> Section<'a> {
+    /// # Panics
+    /// If the range is out of bounds of the shelf.
+    pub fn new(shelf: &'a dyn Shelf, range: impl RangeBounds<usize>) -> Self {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => shelf.len(),
+        };
+        assert!(
+            start <= end && end <= shelf.len(),
+            "Section {start}..{end} out of bounds of a shelf of {} seeds",
+            shelf.len()
+        );
+        Self { shelf, start, end }
+    }
+}
+
+impl Shelf for Section<'_> {
+    fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    fn seed(&self, index: usize) -> Seed<'_> {
+        assert!(index < self.len(), "Seed {index} out of bounds");
+        Seed {
+            index,
+            ..self.shelf.seed(self.start + index)
+        }
+    }
+
+    fn find(&self, root: &str) -> Option<usize> {
+        self.find_in(root, 0..self.len())
+    }
+
+    fn find_in(&self, root: &str, range: Range<usize>) -> Option<usize> {
+        let range = self.start + range.start..self.start + range.end.min(self.len());
+        self.shelf
+            .find_in(root, range)
+            .map(|index| index - self.start)
+    }
+
+    /// The metadata of the whole shelf the section is of.
+    fn metadata(&self) -> Cow<'_, Metadata> {
+        self.shelf.metadata()
+    }
+}
@@ -0,0 +1,82 @@
+use super::{Annotation, Gram};
+use std::cmp::Ordering;
+
+/// The order to rank grams in, see [Librarian::top_k](super::Librarian::top_k).
+///
+/// Each key ranks the grams it favours first, and ties keep the order the query found them in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SortKey {
+    /// Most occurrences first, summed over the words of a sequence.
+    Count,
+    /// Most occurrences first, averaged over the words of a sequence.
+    #[default]
+    CountMean,
+    /// Most characters first.
+    Length,
+    /// Alphabetical order of the roots.
+    Lexicographic,
+    /// Lowest index in the library first.
+    Index,
+    /// Highest [score](Annotation::score) first, then the order the query found them in.
+    Score,
+    /// Fewest [edits](Annotation::distance) first, then most occurrences on average.
+    Distance,
+}
+
+impl SortKey {
+    /// Compare two grams, where [Less](Ordering::Less) ranks `lhs` first.
+    ///
+    /// [Score](Self::Score) and [Distance](Self::Distance) rank by the annotations of the grams,
+    /// putting those without one last.
+    #[must_use]
+    pub fn cmp(self, lhs: (&Gram<'_>, &Annotation), rhs: (&Gram<'_>, &Annotation)) -> Ordering {
+        let ((lhs, lnote), (rhs, rnote)) = (lhs, rhs);
+        match self {
+            SortKey::Count => rhs.cmp_by_count(lhs),
+            SortKey::CountMean => rhs.cmp_by_count_mean(lhs),
+            SortKey::Length => rhs.char_len().cmp(&lhs.char_len()),
+            SortKey::Lexicographic => lhs.cmp_by_lexicographic(rhs),
+            SortKey::Index => lhs.cmp_by_index(rhs),
+            SortKey::Score => {
+                let score = |note: &Annotation| note.score.unwrap_or(f64::NEG_INFINITY);
+                score(rnote).total_cmp(&score(lnote))
+            }
+            SortKey::Distance => {
+                let distance = |note: &Annotation| note.distance.unwrap_or(usize::MAX);
+                distance(lnote)
+                    .cmp(&distance(rnote))
+                    .then_with(|| rhs.cmp_by_count_mean(lhs))
+            }
+        }
+    }
+}
+
+/// A gram in a ranking, ordered by its key and then by its position in the librarian.
+pub(super) struct Ranked<'a, 'l> {
+    pub key: SortKey,
+    pub gram: Gram<'l>,
+    pub note: &'a Annotation,
+    pub position: usize,
+}
+
+impl PartialEq for Ranked<'_, '_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked<'_, '_> {}
+
+impl PartialOrd for Ranked<'_, '_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked<'_, '_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key
+            .cmp((&self.gram, self.note), (&other.gram, other.note))
+            .then(self.position.cmp(&other.position))
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashSet;
 
-use super::{Gram, Librarian};
+use super::{Gram, Librarian, Metadata};
 #[cfg(feature = "serde")]
 use serde::Serialize;
 
@@ -31,11 +31,16 @@ pub struct Stats {
     /// Total number of characters over all ngrams
     pub chars_ngrams: usize,
     pub occurrences: StatsOccurrences,
+    /// Details of how the library was built
+    pub metadata: Metadata,
 }
 
 impl Stats {
     fn new<'a, 'l>(librarian: &'a Librarian<'l>) -> Self {
-        let mut stats = Self::default();
+        let mut stats = Self {
+            metadata: librarian.metadata().into_owned(),
+            ..Self::default()
+        };
 
         let mut unique_seeds = HashSet::<usize>::new();
         for gram in librarian.iter() {
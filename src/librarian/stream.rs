@@ -0,0 +1,197 @@
+use super::{
+    Gram, LibGram, Shelf,
+    query::{Budget, Limit},
+    search::{Indexed, Meter, MultiHeadDFA, Nest, Node},
+};
+use regex_automata::dfa::Automaton;
+use std::{fmt::Debug, iter::FusedIterator};
+
+/// Grams found one at a time by a search.
+pub(super) trait Source<'l>: Iterator<Item = LibGram<'l>> {
+    /// The limit which stopped the search, if it did not run to completion.
+    fn limit(&self) -> Option<Limit>;
+}
+
+/// Walks a search index with an automaton, one accepted gram at a time.
+pub(super) struct Walk<'a, 'l, DFA: Automaton, N: Node<u8>> {
+    search: MultiHeadDFA<DFA, Nest<u8, N>>,
+    grams: &'a [LibGram<'l>],
+}
+
+impl<'a, 'l, DFA: Automaton, N: Node<u8> + Indexed + Debug> Walk<'a, 'l, DFA, N>
+where
+    N::Children: Debug,
+{
+    /// `grams` are those of the librarian the index is over.
+    pub(super) fn new(
+        dfa: DFA,
+        root: N,
+        depth: usize,
+        budget: &Budget,
+        grams: &'a [LibGram<'l>],
+    ) -> Result<Self, regex_automata::dfa::StartError> {
+        Ok(Self {
+            search: MultiHeadDFA::new(dfa, Nest::new(root, depth))?.metered(budget.clone()),
+            grams,
+        })
+    }
+}
+
+impl<'l, DFA: Automaton, N: Node<u8> + Indexed + Debug> Iterator for Walk<'_, 'l, DFA, N>
+where
+    N::Children: Debug,
+{
+    type Item = LibGram<'l>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, _) = self.search.next()?;
+        Some(
+            node.chain()
+                .into_iter()
+                .map(|t| &self.grams[t.index().expect("Returned Nodes are leaves")])
+                .collect(),
+        )
+    }
+}
+
+impl<'l, DFA: Automaton, N: Node<u8> + Indexed + Debug> Source<'l> for Walk<'_, 'l, DFA, N>
+where
+    N::Children: Debug,
+{
+    fn limit(&self) -> Option<Limit> {
+        self.search.limit()
+    }
+}
+
+/// Scans the grams of a librarian in order, keeping those accepted.
+pub(super) struct Scan<'a, 'l, F> {
+    grams: std::slice::Iter<'a, LibGram<'l>>,
+    keep: F,
+    meter: Meter,
+}
+
+impl<'a, 'l, F: FnMut(&LibGram<'l>) -> bool> Scan<'a, 'l, F> {
+    pub(super) fn new(grams: &'a [LibGram<'l>], budget: &Budget, keep: F) -> Self {
+        Self {
+            grams: grams.iter(),
+            keep,
+            meter: Meter::new(budget.clone()),
+        }
+    }
+}
+
+impl<'l, F: FnMut(&LibGram<'l>) -> bool> Iterator for Scan<'_, 'l, F> {
+    type Item = LibGram<'l>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let lgram = self.grams.next()?;
+            if !self.meter.visit() {
+                return None;
+            }
+            if (self.keep)(lgram) {
+                return Some(lgram.clone());
+            }
+        }
+    }
+}
+
+impl<'l, F: FnMut(&LibGram<'l>) -> bool> Source<'l> for Scan<'_, 'l, F> {
+    fn limit(&self) -> Option<Limit> {
+        self.meter.limit()
+    }
+}
+
+/// Grams which were all found up front.
+pub(super) struct Found<'l> {
+    grams: std::vec::IntoIter<LibGram<'l>>,
+    limit: Option<Limit>,
+}
+
+impl<'l> Found<'l> {
+    pub(super) fn new(grams: Vec<LibGram<'l>>, limit: Option<Limit>) -> Self {
+        Self {
+            grams: grams.into_iter(),
+            limit,
+        }
+    }
+}
+
+impl<'l> Iterator for Found<'l> {
+    type Item = LibGram<'l>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.grams.next()
+    }
+}
+
+impl<'l> Source<'l> for Found<'l> {
+    fn limit(&self) -> Option<Limit> {
+        self.limit
+    }
+}
+
+/// A lazy iterator over the grams found by a search, see [Librarian::search_iter](super::Librarian::search_iter).
+///
+/// The search only advances as each gram is taken, so stopping early skips the rest of it.
+#[must_use]
+pub struct Stream<'a, 'l> {
+    library: &'l dyn Shelf,
+    source: Box<dyn Source<'l> + 'a>,
+    /// Grams left before the results of the budget are spent.
+    remaining: Option<usize>,
+    truncated: Option<Limit>,
+}
+
+impl<'a, 'l> Stream<'a, 'l> {
+    pub(super) fn new(
+        library: &'l dyn Shelf,
+        source: Box<dyn Source<'l> + 'a>,
+        budget: &Budget,
+    ) -> Self {
+        Self {
+            library,
+            source,
+            remaining: budget.results,
+            truncated: None,
+        }
+    }
+
+    /// Returns the limit reached if the search was cut short by its [Budget].
+    ///
+    /// This is only known once the stream has ended.
+    #[must_use]
+    pub fn truncated(&self) -> Option<Limit> {
+        self.truncated.or_else(|| self.source.limit())
+    }
+}
+
+impl<'l> Iterator for Stream<'_, 'l> {
+    type Item = Gram<'l>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == Some(0) {
+            // Look one gram ahead to tell a full budget from a finished search.
+            if self.truncated.is_none() && self.source.next().is_some() {
+                self.truncated = Some(Limit::Results);
+            }
+            return None;
+        }
+        let lgram = self.source.next()?;
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= 1;
+        }
+        Some(lgram.into_gram(self.library))
+    }
+}
+
+impl Debug for Stream<'_, '_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Stream")
+            .field("remaining", &self.remaining)
+            .field("truncated", &self.truncated())
+            .finish_non_exhaustive()
+    }
+}
+
+impl FusedIterator for Stream<'_, '_> {}
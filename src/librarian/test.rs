@@ -26,11 +26,29 @@ fn library_from_dataset<'a>(it: impl IntoIterator<Item = &'a str>) -> Library {
     )
 }
 
+/// Returns the roots of the grams in sorted order, for results in no particular order.
+fn sorted_roots(librarian: &Librarian) -> Vec<String> {
+    librarian
+        .iter()
+        .map(|gram| gram.root().into_owned())
+        .sorted()
+        .collect()
+}
+
+/// Returns the roots of the grams and their scores, sorted by root.
+fn sorted_scores(librarian: &Librarian) -> Vec<(String, f64)> {
+    librarian
+        .iter_annotated()
+        .map(|(gram, note)| (gram.root().into_owned(), note.score.unwrap()))
+        .sorted_by(|lhs, rhs| lhs.0.cmp(&rhs.0))
+        .collect()
+}
+
 #[test]
 fn make_library() {
     let dataset = dataset();
     let library = library_from_dataset(dataset.iter().copied());
-    assert_eq!(library.seeds.len(), dataset.len());
+    assert_eq!(library.len(), dataset.len());
 
     // The base librarian should have the same number of grams as the library seeds
     let librarian = Librarian::from(&library);
@@ -45,12 +63,12 @@ fn librarian_iter() {
 
     for (i, gram) in librarian.iter().enumerate() {
         assert!(gram.word().is_some());
-        assert_eq!(gram.word().unwrap(), &library.seeds[i]);
+        assert_eq!(gram.word().unwrap(), library.seed(i));
     }
 
     for (i, gram) in librarian.into_iter().enumerate() {
         assert!(gram.word().is_some());
-        assert_eq!(gram.word().unwrap(), &library.seeds[i]);
+        assert_eq!(gram.word().unwrap(), library.seed(i));
     }
 }
 
@@ -66,7 +84,7 @@ fn search_lvl0() {
     assert_eq!(results.len(), 1);
     assert_eq!(
         results.iter().next().unwrap().word().unwrap(),
-        &library.seeds[2]
+        library.seed(2)
     );
 
     // Search for a sequence
@@ -172,3 +190,1264 @@ fn has() {
     assert_eq!(results.len(), 1);
     assert_eq!(results.iter().next().unwrap().word().unwrap().root, "regex");
 }
+
+#[test]
+fn only() {
+    let library = library_from_dataset(dataset());
+    let librarian = Librarian::from(&library);
+    let scored = |query: query::Only| sorted_scores(&librarian.only(&query).unwrap());
+
+    // Pangrams score a bonus of a point for each letter of the bank.
+    assert_eq!(
+        scored(query::Only::new("spear").min_length(4)),
+        [
+            ("pear".into(), 1.0),
+            ("pears".into(), 10.0),
+            ("spear".into(), 10.0)
+        ]
+    );
+    // Without a minimum length, every gram scores a point per letter.
+    assert_eq!(
+        scored(query::Only::new("spear")),
+        [
+            ("pear".into(), 4.0),
+            ("pears".into(), 10.0),
+            ("spear".into(), 10.0)
+        ]
+    );
+    assert_eq!(
+        scored(query::Only::new("spear").required("s")),
+        [("pears".into(), 10.0), ("spear".into(), 10.0)]
+    );
+    assert_eq!(scored(query::Only::new("tse")), [("test".into(), 7.0)]);
+    assert!(scored(query::Only::new("")).is_empty());
+
+    let pairs = scored(query::Only::new("pear").min_length(7).depth(1));
+    assert_eq!(pairs, [("pear pear".into(), 12.0)]);
+}
+
+#[test]
+fn letters() {
+    let library = library_from_dataset(dataset());
+    let librarian = Librarian::from(&library);
+    let roots = |query: &query::Letters| sorted_roots(&librarian.letters(query).unwrap());
+
+    assert_eq!(
+        roots(&query::Letters::new().exclude("e")),
+        ["gram", "librarian", "library", "rust", "world"]
+    );
+    assert_eq!(
+        roots(&query::Letters::new().distinct(true)),
+        ["gram", "pear", "pears", "rust", "search", "spear", "world"]
+    );
+    assert_eq!(
+        roots(&query::Letters::new().count('r', 2..)),
+        ["librarian", "library"]
+    );
+    assert!(roots(&query::Letters::new().count('e', ..0)).is_empty());
+    assert_eq!(roots(&query::Letters::new()).len(), library.len());
+
+    // The letters of a sequence are counted over all of its words.
+    let twice = query::Letters::new().count('s', 2..);
+    assert!(roots(&twice).is_empty());
+    let pairs = roots(&twice.depth(1));
+    assert!(pairs.contains(&"pears spear".to_owned()));
+    assert!(
+        pairs
+            .iter()
+            .all(|root| root.chars().filter(|&c| c == 's').count() >= 2)
+    );
+
+    assert_eq!(query::Letters::parse_count("s<=2"), Some(('s', 0..=2)));
+    assert_eq!(
+        query::Letters::parse_count("e>=1"),
+        Some(('e', 1..=usize::MAX))
+    );
+    assert_eq!(query::Letters::parse_count("a=2"), Some(('a', 2..=2)));
+    assert_eq!(query::Letters::parse_count("a<2"), None);
+    assert_eq!(query::Letters::parse_count("s"), None);
+
+    // Counting every letter is too much for the automaton, which is left to the histograms.
+    let all = librarian.letters(&query::Letters::new().depth(1)).unwrap();
+    let query = ('a'..='z')
+        .fold(query::Letters::new().depth(1), |query, letter| {
+            query.count(letter, ..=2)
+        })
+        .count('e', 1..=2);
+    let expected = all
+        .iter()
+        .map(|gram| gram.root().replace(' ', ""))
+        .filter(|root| {
+            let histogram = anagram::histogram(root);
+            histogram.contains_key(&'e') && histogram.values().all(|&count| count <= 2)
+        })
+        .count();
+    assert_eq!(librarian.letters(&query).unwrap().len(), expected);
+
+    // Repeats of the most used letters are pruned from the walk, within a budget the full walk exceeds.
+    let budget = query::Budget::new().nodes(1000);
+    let all = query::Letters::new().depth(2);
+    let distinct = librarian
+        .letters(&all.clone().distinct(true).budget(budget.clone()))
+        .unwrap();
+    assert_eq!(distinct.truncated(), None);
+    let expected = librarian
+        .letters(&all)
+        .unwrap()
+        .iter()
+        .map(|gram| gram.root().replace(' ', ""))
+        .filter(|root| anagram::histogram(root).values().all(|&count| count == 1))
+        .count();
+    assert_eq!(distinct.len(), expected);
+    let result = librarian.letters(&all.budget(budget)).unwrap();
+    assert_eq!(result.truncated(), Some(query::Limit::Nodes));
+}
+
+#[test]
+fn complete() {
+    let dataset = dataset();
+    let library = library_from_dataset(dataset.iter().copied());
+    let librarian = Librarian::from(&library);
+
+    let query = query::Prefix::new("pe");
+    let results = librarian.complete(&query).unwrap();
+    let roots = results.iter().map(|gram| gram.root()).collect::<Vec<_>>();
+    // Ranked by count, which increases through the dataset
+    assert_eq!(roots, ["pears", "pear"]);
+
+    let query = query::Prefix::new("lib");
+    let results = librarian.complete(&query).unwrap();
+    assert_eq!(results.len(), 2);
+
+    let query = query::Prefix::new("xyz");
+    let results = librarian.complete(&query).unwrap();
+    assert!(results.is_empty());
+}
+
+#[test]
+fn trie_cached() {
+    let dataset = dataset();
+    let library = library_from_dataset(dataset.iter().copied());
+    let librarian = Librarian::from(&library);
+    assert!(librarian.index.get().is_none(), "Trie is built lazily");
+
+    librarian.nearest(&query::Nearest::new("pear", 1)).unwrap();
+    let index = librarian.index.get().expect("Trie is kept after a query");
+    librarian
+        .distance(&query::Distance::new("pear", [1]))
+        .unwrap();
+    assert!(Arc::ptr_eq(index, librarian.index.get().unwrap()));
+
+    // Unchanged children share the trie
+    let unchanged = librarian.filter(|_| true);
+    assert!(Arc::ptr_eq(index, unchanged.index.get().unwrap()));
+
+    // Filtered children derive their trie, which must match a fresh build
+    let filtered = librarian.blacklist(["pear", "hello"]);
+    let derived = filtered
+        .index
+        .get()
+        .expect("Trie is derived from the parent");
+    assert_eq!(**derived, SearchIndex::Trie(Trie::from(&filtered)));
+
+    let results = filtered.complete(&query::Prefix::new("pe")).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results.iter().next().unwrap().root(), "pears");
+}
+
+#[test]
+fn compact() {
+    let dataset = dataset();
+    let library = library_from_dataset(dataset.iter().copied());
+    let librarian = Librarian::from(&library);
+    let compact = librarian.clone().compact();
+
+    let query = query::Match::new("^(hello|world|pears?)$").depth(1);
+    let expected = librarian.search(&query).unwrap();
+    let results = compact.search(&query).unwrap();
+    assert_eq!(results.len(), expected.len());
+    for gram in expected.iter() {
+        assert!(results.iter().any(|g| g == gram));
+    }
+
+    let query = query::Nearest::new("librar", 5);
+    assert_eq!(
+        compact.nearest(&query).unwrap(),
+        librarian.nearest(&query).unwrap()
+    );
+
+    let query = query::Prefix::new("pe");
+    assert_eq!(
+        compact.complete(&query).unwrap(),
+        librarian.complete(&query).unwrap()
+    );
+
+    // Derived librarians build a compact index of their own
+    let filtered = compact.blacklist(["pear"]);
+    let results = filtered.complete(&query).unwrap();
+    assert_eq!(results.len(), 1);
+    assert!(matches!(filtered.search_index(), SearchIndex::Dawg(_)));
+    assert!(matches!(results.search_index(), SearchIndex::Dawg(_)));
+}
+
+#[test]
+fn pattern() {
+    let library = library_from_dataset(dataset());
+    let librarian = Librarian::from(&library);
+    let roots = |pattern| sorted_roots(&librarian.pattern(&query::Pattern::new(pattern)).unwrap());
+
+    assert_eq!(roots("?ear"), ["pear"]);
+    assert_eq!(roots("*ear?"), ["pears"]);
+    assert_eq!(roots("*ear*"), ["pear", "pears", "search", "spear"]);
+    assert_eq!(roots("r[aeiou]st"), ["rust"]);
+    assert_eq!(roots("[^p]?e*"), ["seed", "spear"]);
+    assert_eq!(roots("[a-h]*"), ["gram", "hello"]);
+    assert!(roots("ear").is_empty());
+    assert!(matches!(
+        librarian.pattern(&query::Pattern::new("[ae")),
+        Err(Error::Pattern(_))
+    ));
+    assert!(matches!(
+        librarian.pattern(&query::Pattern::new("?[]")),
+        Err(Error::Pattern(_))
+    ));
+
+    // Sequences are matched by their total length
+    let pairs = librarian
+        .pattern(&query::Pattern::new("????????").depth(1))
+        .unwrap();
+    let expected = librarian
+        .search(&query::Match::new("^.{8}$").depth(1))
+        .unwrap();
+    assert!(!pairs.is_empty());
+    assert_eq!(pairs.grams, expected.grams);
+    let query = query::Pattern::new("hello?orld").depth(1);
+    assert_eq!(librarian.pattern(&query).unwrap().len(), 1);
+}
+
+#[test]
+fn wordle() {
+    use query::Mark::{Green as G, Grey as X, Yellow as Y};
+
+    let library = library_from_dataset([
+        "speed", "spree", "creep", "crepe", "erase", "eases", "those", "essay", "chess", "sweep",
+    ]);
+    let librarian = Librarian::from(&library);
+    let roots = |query: query::Wordle| sorted_roots(&librarian.wordle(&query).unwrap());
+
+    assert_eq!(
+        roots(query::Wordle::new().guess("speed", [X, Y, G, G, X])),
+        ["creep"]
+    );
+    // The grey `e` pins the answer to exactly one `e`.
+    assert_eq!(
+        roots(query::Wordle::new().guess("sweep", [Y, X, Y, X, X])),
+        ["essay", "those"]
+    );
+    assert_eq!(
+        roots(query::Wordle::new().guess("sweep", [Y, X, Y, Y, X])),
+        ["erase"]
+    );
+    assert_eq!(
+        roots(
+            query::Wordle::new()
+                .guess("sweep", [Y, X, Y, X, X])
+                .guess("essay", [Y, Y, X, X, X])
+        ),
+        ["those"]
+    );
+    assert_eq!(roots(query::Wordle::new()).len(), library.len());
+
+    assert_eq!(
+        query::Wordle::parse_guess("crane:gy..G"),
+        Some(("crane", vec![G, Y, X, X, G]))
+    );
+    assert_eq!(query::Wordle::parse_guess("crane:gyz.g"), None);
+    assert!(matches!(
+        librarian.wordle(&query::Wordle::new().guess("speed", [G, G])),
+        Err(Error::Guess(_))
+    ));
+    assert!(matches!(
+        librarian.wordle(&query::Wordle::new().guess("a", [G]).guess("ab", [G, G])),
+        Err(Error::Guess(_))
+    ));
+}
+
+#[test]
+fn wordle_feedback() {
+    use query::Mark::{Green as G, Grey as X, Yellow as Y};
+    let feedback = |guess: &str, answer: &str| {
+        let code = wordle::feedback(&guess.chars().collect_vec(), &answer.chars().collect_vec());
+        // Each mark is a digit of the code in base 3, the first least significant.
+        (0..guess.len() as u32)
+            .map(|i| [X, Y, G][(code / 3u64.pow(i) % 3) as usize])
+            .collect_vec()
+    };
+
+    assert_eq!(feedback("speed", "abide"), [X, X, Y, X, Y]);
+    assert_eq!(feedback("creep", "creep"), [G; 5]);
+    assert_eq!(feedback("eerie", "there"), [Y, X, Y, X, G]);
+
+    // Every answer is left by the marks its guesses get.
+    let words = [
+        "speed", "spree", "creep", "crepe", "erase", "eases", "those",
+    ];
+    let library = library_from_dataset(words);
+    let librarian = Librarian::from(&library);
+    for guess in words {
+        for answer in words {
+            let query = query::Wordle::new().guess(guess, feedback(guess, answer));
+            let left = librarian.wordle(&query).unwrap();
+            assert!(left.iter().any(|gram| gram.root() == answer));
+        }
+    }
+}
+
+#[test]
+fn rank_guesses() {
+    let library = library_from_dataset(["grind", "crate", "grate", "irate", "slate"]);
+    let librarian = Librarian::from(&library);
+    let (word, marks) = query::Wordle::parse_guess("slate:..ggg").unwrap();
+    let candidates = librarian
+        .wordle(&query::Wordle::new().guess(word, marks))
+        .unwrap();
+    assert_eq!(candidates.len(), 3);
+    let ranked = |query: query::Guesses| {
+        candidates
+            .rank_guesses(&query)
+            .0
+            .into_iter()
+            .map(|(gram, score)| (gram.root().into_owned(), (score * 1000.0).round() / 1000.0))
+            .collect_vec()
+    };
+
+    // Each candidate tells itself apart from the other two, which look alike.
+    assert_eq!(
+        ranked(query::Guesses::new()),
+        [
+            ("crate".into(), 0.918),
+            ("grate".into(), 0.918),
+            ("irate".into(), 0.918)
+        ]
+    );
+    // A word which cannot be the answer tells all three apart.
+    assert_eq!(
+        ranked(query::Guesses::new().any(true).top(2)),
+        [("grind".into(), 1.585), ("crate".into(), 0.918)]
+    );
+    assert_eq!(
+        ranked(
+            query::Guesses::new()
+                .strategy(query::Strategy::Remaining)
+                .any(true)
+        )[..2],
+        [("grind".into(), 1.0), ("crate".into(), 1.333)]
+    );
+    // Guessing one of two candidates finds it half of the time, leaving none.
+    let pair = candidates.filter(|seed| seed.root != "irate");
+    let remaining = query::Guesses::new()
+        .strategy(query::Strategy::Remaining)
+        .any(true);
+    let (guesses, _) = pair.rank_guesses(&remaining);
+    let scores = guesses
+        .iter()
+        .map(|(gram, score)| (gram.root(), *score))
+        .collect::<HashMap<_, _>>();
+    assert_eq!(scores["crate"], 0.5);
+    assert_eq!(scores["grind"], 1.0);
+    // Weighted by count, the most common candidate splits the others evenly.
+    let weighted = ranked(query::Guesses::new().weighted(true).top(1));
+    assert_eq!(weighted[0].0, "irate");
+
+    // Only the guesses scored within the budget are ranked.
+    let budget = query::Budget::new().nodes(3);
+    let (budgeted, limit) =
+        candidates.rank_guesses(&query::Guesses::new().any(true).budget(budget));
+    assert_eq!(limit, Some(query::Limit::Nodes));
+    assert_eq!(budgeted.len(), 3);
+    assert!(budgeted.iter().all(|(gram, _)| gram.root() != "grind"));
+}
+
+#[test]
+fn suffix() {
+    let dataset = dataset();
+    let library = library_from_dataset(dataset.iter().copied());
+    let librarian = Librarian::from(&library);
+
+    let query = query::Suffix::new("ar");
+    let results = librarian.suffix(&query).unwrap();
+    let mut roots = results.iter().map(|gram| gram.root()).collect::<Vec<_>>();
+    roots.sort();
+    assert_eq!(roots, ["pear", "spear"]);
+
+    // Only the end of the gram is matched
+    let query = query::Suffix::new("r");
+    let results = librarian.suffix(&query).unwrap();
+    assert_eq!(results.len(), 2);
+
+    let query = query::Suffix::new("e[ae]r");
+    let results = librarian.suffix(&query).unwrap();
+    assert_eq!(results.len(), 2);
+
+    // Sequences keep their order
+    let query = query::Suffix::new("helloworld").depth(1);
+    let results = librarian.suffix(&query).unwrap();
+    assert_eq!(results.len(), 1);
+    let roots = results.into_iter().next().unwrap().sequence().unwrap();
+    assert_eq!(roots[0].root, "hello");
+    assert_eq!(roots[1].root, "world");
+}
+
+#[test]
+fn range() {
+    let dataset = dataset();
+    let library = library_from_dataset(dataset.iter().copied());
+    let librarian = Librarian::from(&library);
+
+    let query = query::Range::new("pear".."seed");
+    let results = librarian.range(&query).unwrap();
+    let roots = results.iter().map(|gram| gram.root()).collect::<Vec<_>>();
+    assert_eq!(roots, ["pear", "pears", "regex", "rust", "search"]);
+
+    let query = query::Range::new(..).reverse(true);
+    let results = librarian.range(&query).unwrap();
+    let mut sorted = dataset.clone();
+    sorted.sort();
+    sorted.reverse();
+    let roots = results.iter().map(|gram| gram.root()).collect::<Vec<_>>();
+    assert_eq!(roots, sorted);
+
+    let query = query::Range::new("pear"..="rust").reverse(true);
+    let results = librarian.clone().compact().range(&query).unwrap();
+    let roots = results.iter().map(|gram| gram.root()).collect::<Vec<_>>();
+    assert_eq!(roots, ["rust", "regex", "pears", "pear"]);
+}
+
+#[test]
+fn library_lookup() {
+    let dataset = dataset();
+    let library = library_from_dataset(dataset.iter().copied());
+
+    for (i, root) in dataset.iter().enumerate() {
+        assert!(library.contains(root));
+        assert_eq!(library.get(root), Some(library.seed(i)));
+    }
+    assert!(!library.contains("missing"));
+    assert_eq!(library.get("missing"), None);
+
+    let librarian = Librarian::from(&library);
+    assert_eq!(librarian.root("pear"), library.get("pear"));
+
+    let results = librarian.whitelist(["pear", "spear", "missing"]);
+    assert_eq!(results.len(), 2);
+    let results = librarian.blacklist(["pear", "spear", "missing"]);
+    assert_eq!(results.len(), dataset.len() - 2);
+}
+
+#[test]
+fn library_mutation() {
+    let dataset = dataset();
+    let mut library = library_from_dataset(dataset.iter().copied());
+    let before = library.clone();
+
+    assert_eq!(library.insert("pear", 5), 10);
+    assert_eq!(library.seed(10).count, 15);
+    assert_eq!(library.insert("peach", 2), dataset.len());
+    assert_eq!(
+        library.get("peach").map(|seed| seed.index),
+        Some(dataset.len())
+    );
+
+    library.extend([("plum".to_owned(), 1), ("peach".to_owned(), 3)]);
+    assert_eq!(library.len(), dataset.len() + 2);
+    assert_eq!(library.get("peach").unwrap().count, 5);
+
+    let other = library_from_dataset(["plum", "hello", "quince"]);
+    library.merge(&other);
+    assert_eq!(library.len(), dataset.len() + 3);
+    assert_eq!(library.get("plum").unwrap().count, 1);
+    assert_eq!(library.get("quince").unwrap().index, dataset.len() + 2);
+    assert_eq!(library.seed(0).count, 1);
+
+    // Existing seeds keep their index and root.
+    for seed in before.iter() {
+        assert_eq!(library.seed(seed.index).root, seed.root);
+        assert_eq!(library.find(seed.root), Some(seed.index));
+    }
+    let librarian = Librarian::from(&library);
+    assert_eq!(
+        librarian
+            .search(&query::Match::new("^p.*ch$"))
+            .unwrap()
+            .iter()
+            .map(|gram| gram.root().into_owned())
+            .collect_vec(),
+        ["peach"]
+    );
+}
+
+#[test]
+fn library_metadata() {
+    let metadata = Metadata {
+        name: Some("fruit".into()),
+        sources: vec!["fruit.txt".into(), "more fruit.txt".into()],
+        tokens: Some(120),
+        threshold: Some(2),
+        case_folded: Some(true),
+        language: Some("en".into()),
+    };
+    let library = library_from_dataset(dataset()).with_metadata(metadata.clone());
+
+    let mut text = Vec::new();
+    metadata.write_comments(&mut text).unwrap();
+    text.extend(b"pear\t1\n");
+    // A buffer of a byte splits every comment across refills.
+    let reader = std::io::BufReader::with_capacity(1, text.as_slice());
+    let (read, mut seeds) = Metadata::read_comments(reader).unwrap();
+    assert_eq!(read, metadata);
+    let mut rest = String::new();
+    std::io::Read::read_to_string(&mut seeds, &mut rest).unwrap();
+    assert_eq!(rest, "pear\t1\n");
+
+    let mut bytes = Vec::new();
+    library.write_binary(&mut bytes, true).unwrap();
+    let binary = binary::BinaryLibrary::new(&bytes).unwrap();
+    assert_eq!(*binary.metadata(), metadata);
+    assert_eq!(Library::from(binary), library);
+
+    let librarian = Librarian::from(&library);
+    assert_eq!(librarian.stats().metadata, metadata);
+    let section = Section::new(&library, 2..4);
+    assert_eq!(*section.metadata(), metadata);
+
+    let mut merged = library_from_dataset(["pear"]).with_metadata(Metadata {
+        sources: vec!["fruit.txt".into()],
+        tokens: Some(10),
+        ..Default::default()
+    });
+    merged.merge(&library);
+    assert_eq!(merged.metadata().sources, metadata.sources);
+    assert_eq!(merged.metadata().tokens, Some(130));
+
+    merged.metadata_mut().tokens = Some(u64::MAX);
+    merged.merge(&library);
+    assert_eq!(merged.metadata().tokens, None);
+}
+
+#[test]
+fn binary_roundtrip() {
+    let dataset = dataset();
+    let library = library_from_dataset(dataset.iter().copied().chain(["pear"]));
+
+    for index in [true, false] {
+        let mut bytes = Vec::new();
+        library.write_binary(&mut bytes, index).unwrap();
+        let binary = binary::BinaryLibrary::new(&bytes).unwrap();
+        assert_eq!(binary.has_index(), index);
+        assert_eq!(binary.len(), dataset.len() + 1);
+
+        for (i, root) in dataset.iter().enumerate() {
+            assert_eq!(binary.find(root), Some(i));
+            assert_eq!(binary.root(i), *root);
+            assert_eq!(binary.count(i), i as u64);
+        }
+        assert_eq!(binary.find("missing"), None);
+        assert_eq!(Library::from(binary), library);
+    }
+
+    let mut bytes = Vec::new();
+    Library::from_iter([])
+        .write_binary(&mut bytes, true)
+        .unwrap();
+    let binary = binary::BinaryLibrary::new(&bytes).unwrap();
+    assert!(binary.is_empty());
+    assert_eq!(binary.find("missing"), None);
+}
+
+#[test]
+fn binary_corrupt() {
+    let library = library_from_dataset(dataset());
+    let mut bytes = Vec::new();
+    library.write_binary(&mut bytes, true).unwrap();
+
+    let mut magic = bytes.clone();
+    magic[0] = b'X';
+    assert!(matches!(
+        binary::BinaryLibrary::new(&magic),
+        Err(Error::BadMagic)
+    ));
+
+    let mut version = bytes.clone();
+    version[8] = 0;
+    assert!(matches!(
+        binary::BinaryLibrary::new(&version),
+        Err(Error::Version(0, binary::VERSION))
+    ));
+
+    let mut flipped = bytes.clone();
+    *flipped.last_mut().unwrap() ^= 1;
+    assert!(matches!(
+        binary::BinaryLibrary::new(&flipped),
+        Err(Error::Checksum { .. })
+    ));
+
+    assert!(matches!(
+        binary::BinaryLibrary::new(&bytes[..bytes.len() - 1]),
+        Err(Error::Corrupt(_))
+    ));
+    assert!(matches!(
+        binary::BinaryLibrary::new(&bytes[..20]),
+        Err(Error::Corrupt(_))
+    ));
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn binary_mapped() {
+    let library = library_from_dataset(dataset());
+    let path = std::env::temp_dir().join(format!("grumpr-{}.grumpr", std::process::id()));
+    library
+        .write_binary(std::fs::File::create(&path).unwrap(), true)
+        .unwrap();
+
+    let mapped = binary::MappedLibrary::open(&path).unwrap();
+    assert!(mapped.verify().is_ok());
+    assert_eq!(mapped.find("spear"), Some(12));
+    assert_eq!(Library::from(mapped.library()), library);
+    drop(mapped);
+
+    // Opening does not read the counts, the checksum is left to verify.
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes[48] ^= 1;
+    std::fs::write(&path, bytes).unwrap();
+    let mapped = binary::MappedLibrary::open(&path).unwrap();
+    assert!(matches!(mapped.verify(), Err(Error::Checksum { .. })));
+    drop(mapped);
+
+    // But the offsets are, so the roots read cannot lie outside the file.
+    let mut bytes = std::fs::read(&path).unwrap();
+    let offset = 48 + library.len() * 8 + 8;
+    bytes[offset..offset + 8].copy_from_slice(&9999u64.to_le_bytes());
+    std::fs::write(&path, bytes).unwrap();
+    assert!(matches!(
+        binary::MappedLibrary::open(&path),
+        Err(Error::Corrupt(_))
+    ));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn shelves() {
+    let dataset = dataset();
+    let library = library_from_dataset(dataset.iter().copied());
+    let mut bytes = Vec::new();
+    library.write_binary(&mut bytes, true).unwrap();
+    let binary = binary::BinaryLibrary::new(&bytes).unwrap();
+
+    let query = query::Match::new("s?pears?");
+    let roots = |librarian: Librarian| {
+        let results = librarian.search(&query).unwrap();
+        results
+            .iter()
+            .map(|gram| gram.word().unwrap())
+            .map(|seed| (seed.root.to_owned(), seed.index, seed.count))
+            .collect::<Vec<_>>()
+    };
+    let expected = roots(Librarian::from(&library));
+    assert_eq!(expected.len(), 3);
+    assert_eq!(roots(Librarian::from(&binary)), expected);
+    assert_eq!(roots(Librarian::new(&binary as &dyn Shelf)), expected);
+
+    // Sections index their seeds from the start of the range.
+    let section = Section::new(&library, 10..);
+    assert_eq!(section.len(), 3);
+    assert_eq!(section.find("pears"), Some(1));
+    assert_eq!(section.find("hello"), None);
+
+    // A root repeated within a section is found there, even if its first seed lies before it.
+    let repeated = library_from_dataset(["pear", "spear", "pear", "plum"]);
+    let after = Section::new(&repeated, 1..);
+    assert_eq!(after.find("pear"), Some(1));
+    assert_eq!(after.find("plum"), Some(2));
+    assert_eq!(Section::new(&repeated, 1..2).find("pear"), None);
+    assert_eq!(Section::new(&after, 1..).find("pear"), Some(0));
+    let results = roots(Librarian::from(&section));
+    assert_eq!(
+        results,
+        expected
+            .iter()
+            .map(|(root, index, count)| (root.clone(), index - 10, *count))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn set_algebra() {
+    let dataset = dataset();
+    let library = library_from_dataset(dataset.iter().copied());
+    let librarian = Librarian::from(&library);
+
+    let roots = |librarian: &Librarian| {
+        librarian
+            .iter()
+            .map(|gram| gram.root().into_owned())
+            .collect::<Vec<_>>()
+    };
+    let pears = librarian.search(&query::Match::new(".*pear.*")).unwrap();
+    let short = librarian.search(&query::Match::new("^.{4}$")).unwrap();
+    assert_eq!(roots(&pears), ["pear", "pears", "spear"]);
+    assert_eq!(roots(&short), ["gram", "rust", "test", "seed", "pear"]);
+
+    assert_eq!(
+        roots(&pears.union(&short)),
+        ["pear", "pears", "spear", "gram", "rust", "test", "seed"]
+    );
+    assert_eq!(roots(&pears.intersection(&short)), ["pear"]);
+    assert_eq!(roots(&pears.difference(&short)), ["pears", "spear"]);
+    assert_eq!(
+        roots(&pears.symmetric_difference(&short)),
+        ["pears", "spear", "gram", "rust", "test", "seed"]
+    );
+    assert_eq!(pears.union(&pears), pears);
+    assert!(pears.difference(&pears).is_empty());
+}
+
+#[test]
+fn budget() {
+    use query::{Budget, CancelToken, Limit};
+    let dataset = dataset();
+    let library = library_from_dataset(dataset.iter().copied());
+    let librarian = Librarian::from(&library);
+
+    let query = query::Match::new("(pear|spear)+").depth(3);
+    let full = librarian.search(&query).unwrap();
+    assert_eq!(full.truncated(), None);
+
+    // A generous budget changes nothing.
+    let roomy = Budget::new().results(full.len()).nodes(1_000_000);
+    let result = librarian.search(&query.clone().budget(roomy)).unwrap();
+    assert_eq!(result, full);
+    assert_eq!(result.truncated(), None);
+
+    let result = librarian
+        .search(&query.clone().budget(Budget::new().results(2)))
+        .unwrap();
+    assert_eq!(result.truncated(), Some(Limit::Results));
+    assert_eq!(result.len(), 2);
+    assert!(result.iter().all(|gram| full.iter().contains(&gram)));
+
+    let result = librarian
+        .search(&query.clone().budget(Budget::new().nodes(10)))
+        .unwrap();
+    assert_eq!(result.truncated(), Some(Limit::Nodes));
+    assert!(result.len() < full.len());
+
+    let token = CancelToken::new();
+    token.cancel();
+    let result = librarian
+        .search(&query.clone().budget(Budget::new().cancel(token)))
+        .unwrap();
+    assert_eq!(result.truncated(), Some(Limit::Cancelled));
+    assert!(result.is_empty());
+
+    // Front ends report the limit by its name, which does not change.
+    assert_eq!(
+        [
+            Limit::Results,
+            Limit::Nodes,
+            Limit::Deadline,
+            Limit::Cancelled
+        ]
+        .map(|l| l.to_string()),
+        ["results", "nodes", "deadline", "cancelled"]
+    );
+
+    // Flat searches and anagrams are budgeted too, and the flag follows derived librarians.
+    let result = librarian
+        .search(&query::Match::new("e").budget(Budget::new().results(1)))
+        .unwrap();
+    assert_eq!(result.truncated(), Some(Limit::Results));
+    assert_eq!(result.len(), 1);
+    assert_eq!(
+        full.union(&result).truncated(),
+        Some(Limit::Results),
+        "union with a truncated librarian"
+    );
+    assert_eq!(
+        result.filter(|_| true).truncated(),
+        Some(Limit::Results),
+        "filtered from a truncated librarian"
+    );
+
+    let anagrams = query::Anagram::new("pearspear").depth(2);
+    assert_eq!(librarian.anagrams(&anagrams).unwrap().truncated(), None);
+    let result = librarian
+        .anagrams(&anagrams.budget(Budget::new().nodes(5)))
+        .unwrap();
+    assert_eq!(result.truncated(), Some(Limit::Nodes));
+
+    // As are suffixes, completions, which keep the most common, and fuzzy searches.
+    let suffix = query::Suffix::new("s").depth(2);
+    let result = librarian
+        .suffix(&suffix.budget(Budget::new().results(1)))
+        .unwrap();
+    assert_eq!(result.truncated(), Some(Limit::Results));
+    assert_eq!(result.len(), 1);
+
+    let prefix = query::Prefix::new("pe");
+    let complete = librarian.complete(&prefix).unwrap();
+    let result = librarian
+        .complete(&prefix.budget(Budget::new().results(1)))
+        .unwrap();
+    assert_eq!(result.truncated(), Some(Limit::Results));
+    assert!(result.iter().eq(complete.iter().take(1)));
+
+    let distance = query::Distance::new("pear", [1]);
+    let result = librarian
+        .distance(&distance.budget(Budget::new().nodes(3)))
+        .unwrap();
+    assert_eq!(result.truncated(), Some(Limit::Nodes));
+}
+
+#[test]
+fn stream() {
+    use query::{Budget, Limit};
+    let dataset = dataset();
+    let library = library_from_dataset(dataset.iter().copied());
+    let librarian = Librarian::from(&library);
+
+    for depth in [0, 3] {
+        let query = query::Match::new("(pear|spear|s)+").depth(depth);
+        let full = librarian.search(&query).unwrap();
+        let stream = librarian.search_iter(&query).unwrap();
+        assert!(stream.eq(full.iter()), "depth {depth}");
+
+        let mut stream = librarian
+            .search_iter(&query.clone().budget(Budget::new().results(2)))
+            .unwrap();
+        assert!(stream.by_ref().eq(full.iter().take(2)), "depth {depth}");
+        assert_eq!(stream.truncated(), Some(Limit::Results));
+    }
+
+    // Taking from the stream stops the search early, within a budget the full search exceeds.
+    let query = query::Match::new("(pear|spear|s)+")
+        .depth(5)
+        .budget(Budget::new().nodes(100));
+    assert_eq!(
+        librarian.search(&query).unwrap().truncated(),
+        Some(Limit::Nodes)
+    );
+    let mut stream = librarian.search_iter(&query).unwrap();
+    assert!(stream.next().is_some());
+    assert_eq!(stream.truncated(), None);
+
+    // Long anagrams are found up front, in no particular order.
+    let roots = |grams: &mut dyn Iterator<Item = Gram>| {
+        grams
+            .map(|gram| gram.root().into_owned())
+            .sorted()
+            .collect_vec()
+    };
+    for pattern in ["pearspear", "aeprs"] {
+        let anagrams = query::Anagram::new(pattern).depth(2);
+        assert_eq!(
+            roots(&mut librarian.anagrams_iter(&anagrams).unwrap()),
+            roots(&mut librarian.anagrams(&anagrams).unwrap().into_iter()),
+            "{pattern}"
+        );
+    }
+}
+
+#[test]
+fn parallel() {
+    // A budget, however large, keeps a search on one thread.
+    let sequential = query::Budget::new().nodes(usize::MAX);
+    let dataset = dataset();
+    let library = library_from_dataset(dataset.iter().copied());
+    let librarian = Librarian::from(&library);
+
+    for librarian in [librarian.clone(), librarian.compact()] {
+        for (pattern, depth) in [("(pear|spear|s)+", 3), ("^.*e.*$", 0), (".*r.*", 2)] {
+            let query = query::Match::new(pattern).depth(depth);
+            assert_eq!(
+                librarian.search(&query).unwrap(),
+                librarian
+                    .search(&query.clone().budget(sequential.clone()))
+                    .unwrap(),
+                "{pattern} {depth}"
+            );
+        }
+        for (pattern, depth) in [("pears", 2), ("pearspear", 2), ("sear", 0)] {
+            let query = query::Anagram::new(pattern).depth(depth);
+            assert_eq!(
+                librarian.anagrams(&query).unwrap(),
+                librarian
+                    .anagrams(&query.clone().budget(sequential.clone()))
+                    .unwrap(),
+                "{pattern} {depth}"
+            );
+        }
+    }
+}
+
+#[test]
+fn top_k() {
+    let dataset = dataset();
+    let library = library_from_dataset(dataset.iter().copied());
+    let librarian = Librarian::from(&library);
+
+    let roots = |librarian: Librarian| {
+        librarian
+            .iter()
+            .map(|gram| gram.root().into_owned())
+            .collect::<Vec<_>>()
+    };
+    // Counts are the index of each word in the dataset.
+    assert_eq!(
+        roots(librarian.top_k(3, SortKey::Count)),
+        ["spear", "pears", "pear"]
+    );
+    assert_eq!(
+        roots(librarian.top_k(3, SortKey::Lexicographic)),
+        ["gram", "hello", "librarian"]
+    );
+    assert_eq!(
+        roots(librarian.top_k(2, SortKey::Index)),
+        ["hello", "world"]
+    );
+    // Ties keep the order of the librarian.
+    assert_eq!(
+        roots(librarian.top_k(3, SortKey::Length)),
+        ["librarian", "library", "search"]
+    );
+    let reversed = librarian
+        .range(&query::Range::new(..).reverse(true))
+        .unwrap();
+    assert_eq!(roots(reversed.top_k(2, SortKey::Score)), ["world", "test"]);
+
+    // Ranking everything matches a full stable sort.
+    for key in [SortKey::Count, SortKey::CountMean, SortKey::Length] {
+        let sorted = librarian
+            .iter_annotated()
+            .sorted_by(|(lhs, lnote), (rhs, rnote)| key.cmp((lhs, lnote), (rhs, rnote)))
+            .map(|(gram, _)| gram)
+            .collect_vec();
+        assert_eq!(
+            librarian.top_k(usize::MAX, key).into_iter().collect_vec(),
+            sorted
+        );
+    }
+    assert!(librarian.top_k(0, SortKey::Count).is_empty());
+}
+
+#[test]
+fn top_seeds() {
+    let tied = Library::from_iter(
+        [("a", 2), ("b", 1), ("c", 1), ("d", 1)].map(|(root, count)| (root.to_owned(), count)),
+    );
+    let librarian = Librarian::from(&tied);
+    let roots = |librarian: Librarian| {
+        librarian
+            .iter()
+            .map(|gram| gram.root().into_owned())
+            .collect::<Vec<_>>()
+    };
+
+    // Ties are ranked by their order in the library, so exactly `n` seeds are kept.
+    assert_eq!(roots(librarian.top_seeds(2)), ["a", "b"]);
+    assert_eq!(roots(librarian.without_top_seeds(2)), ["c", "d"]);
+    assert_eq!(roots(librarian.top_seeds(4)), ["a", "b", "c", "d"]);
+    assert!(librarian.without_top_seeds(4).is_empty());
+
+    // A sequence is only kept if every seed in it is.
+    let pairs = librarian
+        .search(&query::Match::new("^(a|c)(a|c)$").depth(1))
+        .unwrap();
+    assert_eq!(roots(pairs.top_seeds(1)), ["a a"]);
+    assert_eq!(roots(pairs.without_top_seeds(1)), ["c c"]);
+}
+
+#[test]
+fn annotations() {
+    let dataset = dataset();
+    let library = library_from_dataset(dataset.iter().copied());
+    let librarian = Librarian::from(&library);
+    assert!(!librarian.is_annotated());
+
+    let notes = |librarian: &Librarian| {
+        librarian
+            .iter_annotated()
+            .map(|(gram, note)| (gram.root().into_owned(), note.clone()))
+            .collect::<Vec<_>>()
+    };
+
+    let fuzzy = librarian
+        .distance(&query::Distance::new("pearl", [1, 2]).strict(true))
+        .unwrap();
+    assert_eq!(
+        notes(&fuzzy),
+        [
+            ("pear".to_owned(), Annotation::distance(1)),
+            ("pears".to_owned(), Annotation::distance(1)),
+        ]
+    );
+    // Fewest edits first, then the most common.
+    let ranked = fuzzy.top_k(2, SortKey::Distance);
+    assert_eq!(
+        ranked
+            .iter()
+            .map(|gram| gram.root().into_owned())
+            .collect_vec(),
+        ["pears", "pear"]
+    );
+
+    // Later queries add to the annotations, and keep them through set operations.
+    let matched = fuzzy.search(&query::Match::new("ea")).unwrap();
+    let (_, note) = matched.iter_annotated().nth(1).unwrap();
+    assert_eq!(note.distance, Some(1));
+    assert_eq!(note.span, Some(1..3));
+    let union = librarian
+        .search(&query::Match::new("^hello$"))
+        .unwrap()
+        .union(&matched);
+    assert_eq!(union.iter_annotated().last().unwrap().1.span, Some(1..3));
+    assert_eq!(union.iter_annotated().next().unwrap().1.span, Some(0..5));
+
+    let (nearest, distance) = librarian.nearest(&query::Nearest::new("seeds", 2)).unwrap();
+    assert_eq!(distance, 1);
+    assert_eq!(
+        notes(&nearest),
+        [("seed".to_owned(), Annotation::distance(1))]
+    );
+
+    let anagrams = librarian
+        .anagrams(&query::Anagram::new("pea").wildcards(2))
+        .unwrap();
+    assert!(
+        anagrams
+            .iter_annotated()
+            .all(|(_, note)| note.wildcards == Some(2))
+    );
+    let partial = librarian
+        .anagrams(&query::Anagram::new("pear").wildcards(1).partial(true))
+        .unwrap();
+    for (gram, note) in partial.iter_annotated() {
+        let expected = usize::from(gram.char_len() > 4 || gram.root() == "gram");
+        assert_eq!(note.wildcards, Some(expected), "{}", gram.root());
+    }
+
+    let complete = librarian.complete(&query::Prefix::new("pe")).unwrap();
+    assert_eq!(
+        complete
+            .iter_annotated()
+            .map(|(_, note)| note.score)
+            .collect_vec(),
+        [Some(11.0), Some(10.0)]
+    );
+    assert_eq!(
+        complete.top_k(1, SortKey::Score).iter().next(),
+        complete.iter().next()
+    );
+}
+
+#[test]
+fn query_expr() {
+    use query::QueryExpr;
+
+    let dataset = dataset();
+    let library = library_from_dataset(dataset.iter().copied());
+    let librarian = Librarian::from(&library);
+    let roots = |librarian: &Librarian| {
+        librarian
+            .iter()
+            .map(|gram| gram.root().into_owned())
+            .sorted()
+            .collect::<Vec<_>>()
+    };
+
+    // Merged flat matches give the same grams as searching separately.
+    let expr = QueryExpr::from(query::Match::new("^s")) & query::Match::new("r$");
+    assert_eq!(roots(&librarian.query(&expr).unwrap()), ["spear"]);
+    let expr = QueryExpr::from(query::Match::new("^s")) | query::Match::new("r$");
+    assert_eq!(
+        roots(&librarian.query(&expr).unwrap()),
+        ["pear", "search", "seed", "spear"]
+    );
+
+    // Merged matches are annotated as if they were searched separately.
+    let annotated = |librarian: Librarian| {
+        librarian
+            .iter_annotated()
+            .map(|(gram, note)| (gram.root().into_owned(), note.clone()))
+            .sorted_by(|lhs, rhs| lhs.0.cmp(&rhs.0))
+            .collect_vec()
+    };
+    let (lhs, rhs) = (query::Match::new("^s"), query::Match::new("e"));
+    let separate = librarian.search(&lhs).unwrap().search(&rhs).unwrap();
+    let expr = QueryExpr::from(lhs.clone()) & rhs.clone();
+    assert_eq!(
+        annotated(librarian.query(&expr).unwrap()),
+        annotated(separate)
+    );
+    let separate = librarian
+        .search(&lhs)
+        .unwrap()
+        .union(&librarian.search(&rhs).unwrap());
+    let expr = QueryExpr::from(lhs) | rhs;
+    assert_eq!(
+        annotated(librarian.query(&expr).unwrap()),
+        annotated(separate)
+    );
+
+    // Each part of an `And` is searched over the results of the last.
+    let expr = QueryExpr::from(query::Anagram::new("pears"))
+        & !QueryExpr::from(query::Match::new("^s"))
+        & query::Has::new("a");
+    assert_eq!(roots(&librarian.query(&expr).unwrap()), ["pears"]);
+
+    // Deep matches of the same depth are one walk.
+    let expr = QueryExpr::from(query::Match::new("^pearpears$").depth(1))
+        | query::Match::new("^testseed$").depth(1)
+        | query::Anagram::new("gram");
+    let results = librarian.query(&expr).unwrap();
+    assert_eq!(roots(&results), ["gram", "pear pears", "test seed"]);
+
+    let expr = !(QueryExpr::from(query::Match::new("e")) | query::Nearest::new("zzzzzzzz", 1));
+    assert_eq!(
+        roots(&librarian.query(&expr).unwrap()),
+        ["gram", "librarian", "library", "rust", "world"]
+    );
+    assert!(librarian.query(&QueryExpr::Or(vec![])).unwrap().is_empty());
+    assert_eq!(librarian.query(&QueryExpr::And(vec![])).unwrap(), librarian);
+}
+
+#[test]
+fn pipeline_parse() {
+    use pipeline::{Pipeline, Stage};
+    use query::QueryExpr;
+
+    let pipeline =
+        Pipeline::parse("anna pears -w1 | match '^s' -d 2| cross s?e*| only pears -r s -m4| letters -x e -u -c s<=2,a=1 -d2| wordle crane:ggggg speed:yyyyy| not has e|top 100").unwrap();
+    assert_eq!(
+        pipeline.stages,
+        [
+            Stage::Query(query::Anagram::new("pears").wildcards(1).into()),
+            Stage::Query(query::Match::new("^s").depth(1).into()),
+            Stage::Query(query::Pattern::new("s?e*").into()),
+            Stage::Query(query::Only::new("pears").required("s").min_length(4).into()),
+            Stage::Query(
+                query::Letters::new()
+                    .exclude("e")
+                    .distinct(true)
+                    .count('s', 0..=2)
+                    .count('a', 1..=1)
+                    .depth(1)
+                    .into()
+            ),
+            Stage::Query(
+                query::Wordle::new()
+                    .guess("crane", [query::Mark::Green; 5])
+                    .guess("speed", [query::Mark::Yellow; 5])
+                    .into()
+            ),
+            Stage::Query(!QueryExpr::from(query::Has::new("e"))),
+            Stage::Top(100),
+        ]
+    );
+
+    let pipeline =
+        Pipeline::parse(r#"fuzzy "a b|c" --edits=1,2 | fuzzy abc -m 1 | count 3"#).unwrap();
+    assert_eq!(
+        pipeline.stages,
+        [
+            Stage::Query(query::Distance::new("a b|c", [1, 2]).strict(true).into()),
+            Stage::Query(query::Nearest::new("abc", 1).into()),
+            Stage::Count(3),
+        ]
+    );
+    assert!(Pipeline::parse("  ").unwrap().stages.is_empty());
+}
+
+#[test]
+fn pipeline_errors() {
+    use pipeline::{ParseErrorKind, Pipeline};
+
+    let error = |input| {
+        let err = Pipeline::parse(input).unwrap_err();
+        (err.span, err.kind)
+    };
+    assert_eq!(
+        error("match a | | has b"),
+        (10..11, ParseErrorKind::EmptyStage)
+    );
+    assert_eq!(error("match a |"), (9..9, ParseErrorKind::EmptyStage));
+    assert_eq!(
+        error("match 'abc"),
+        (6..10, ParseErrorKind::UnterminatedQuote)
+    );
+    assert_eq!(
+        error("has a | find b"),
+        (8..12, ParseErrorKind::UnknownCommand("find".into()))
+    );
+    assert_eq!(
+        error("match"),
+        (5..5, ParseErrorKind::MissingArgument("pattern"))
+    );
+    assert_eq!(
+        error("match a b"),
+        (8..9, ParseErrorKind::UnexpectedArgument("b".into()))
+    );
+    assert_eq!(
+        error("anna abc -x"),
+        (9..11, ParseErrorKind::UnknownFlag("-x".into()))
+    );
+    assert_eq!(
+        error("anna abc -w"),
+        (9..11, ParseErrorKind::MissingValue("-w".into()))
+    );
+    assert_eq!(
+        error("anna abc -wx"),
+        (11..12, ParseErrorKind::InvalidNumber("x".into()))
+    );
+    assert_eq!(
+        error("anna abc -p1"),
+        (9..12, ParseErrorKind::UnexpectedValue("-p1".into()))
+    );
+    assert_eq!(
+        error("not top 3"),
+        (4..7, ParseErrorKind::NotNegatable("top".into()))
+    );
+}
+
+#[test]
+fn pipeline_run() {
+    let dataset = dataset();
+    let library = library_from_dataset(dataset.iter().copied());
+    let librarian = Librarian::from(&library);
+
+    let pipeline = pipeline::Pipeline::parse("anna pears | not match '^s' | top 12").unwrap();
+    let results = librarian.run(&pipeline, &query::Budget::new()).unwrap();
+    let roots = results.iter().map(|gram| gram.root()).collect::<Vec<_>>();
+    assert_eq!(roots, ["pears"]);
+
+    let pipeline = pipeline::Pipeline::parse("top 2").unwrap();
+    let results = librarian.run(&pipeline, &query::Budget::new()).unwrap();
+    let roots = results.iter().map(|gram| gram.root()).collect::<Vec<_>>();
+    assert_eq!(roots, ["pears", "spear"]);
+
+    // Ties are ranked by their order in the library, so there are still `n` seeds.
+    let tied = Library::from_iter(
+        [("a", 2), ("b", 1), ("c", 1), ("d", 1)].map(|(root, count)| (root.to_owned(), count)),
+    );
+    let results = Librarian::from(&tied)
+        .run(&pipeline, &query::Budget::new())
+        .unwrap();
+    let roots = results.iter().map(|gram| gram.root()).collect::<Vec<_>>();
+    assert_eq!(roots, ["a", "b"]);
+
+    // Every query is given the budget, and the truncation follows the later stages.
+    let pipeline = pipeline::Pipeline::parse("match '(pear|spear)+' -d4 | top 12").unwrap();
+    let budget = query::Budget::new().nodes(10);
+    let results = librarian.run(&pipeline, &budget).unwrap();
+    assert_eq!(results.truncated(), Some(query::Limit::Nodes));
+}
@@ -0,0 +1,222 @@
+use super::{
+    Error, Result, Searched,
+    query::{Budget, Mark, Strategy, Wordle},
+    search::Meter,
+};
+use std::{
+    collections::{BTreeSet, HashMap},
+    ops::RangeInclusive,
+};
+
+/// What the feedback on every guess says of a letter position of the answer.
+#[derive(Debug, Clone)]
+enum Position {
+    /// A letter marked green here.
+    Known(char),
+    /// Letters marked yellow or grey here.
+    Not(BTreeSet<char>),
+}
+
+/// The answers left by a [Wordle] query.
+///
+/// The letters in each position are matched by an automaton,
+/// and the number of each letter by the [histogram](super::anagram::histogram) of the answer.
+#[derive(Debug, Clone)]
+pub(crate) struct Constraints {
+    positions: Vec<Position>,
+    /// Fewest and most of each letter known to be in, or not in, the answer.
+    pub counts: HashMap<char, RangeInclusive<usize>>,
+}
+
+impl Constraints {
+    pub fn new(query: &Wordle<'_>) -> Result<Self> {
+        let mut positions = Vec::new();
+        let mut counts = HashMap::<char, RangeInclusive<usize>>::new();
+
+        for (word, marks) in &query.guesses {
+            let letters = word.chars().collect::<Vec<_>>();
+            if letters.len() != marks.len() {
+                return Err(Error::Guess("each letter of a guess needs one mark"));
+            }
+            if positions.is_empty() {
+                positions = vec![Position::Not(BTreeSet::new()); letters.len()];
+            } else if positions.len() != letters.len() {
+                return Err(Error::Guess("guesses differ in length"));
+            }
+
+            // The letters of the guess which are in the answer, and whether it has any more of them.
+            let mut found = HashMap::<char, (usize, bool)>::new();
+            for ((&c, &mark), position) in letters.iter().zip(marks).zip(&mut positions) {
+                let (found, exact) = found.entry(c).or_default();
+                match mark {
+                    Mark::Green => {
+                        *found += 1;
+                        *position = Position::Known(c);
+                    }
+                    Mark::Yellow | Mark::Grey => {
+                        if mark == Mark::Yellow {
+                            *found += 1;
+                        } else {
+                            *exact = true;
+                        }
+                        if let Position::Not(letters) = position {
+                            letters.insert(c);
+                        }
+                    }
+                }
+            }
+
+            for (c, (found, exact)) in found {
+                let most = if exact { found } else { usize::MAX };
+                let bound = counts.entry(c).or_insert(0..=usize::MAX);
+                *bound = *bound.start().max(&found)..=*bound.end().min(&most);
+            }
+        }
+
+        // A letter not in the answer can be left out of every position.
+        for position in &mut positions {
+            if let Position::Not(letters) = position {
+                letters.extend(
+                    counts
+                        .iter()
+                        .filter(|(_, bound)| *bound.end() == 0)
+                        .map(|(&c, _)| c),
+                );
+            }
+        }
+
+        Ok(Self { positions, counts })
+    }
+
+    /// A regex matching the letters each position may hold, anchored to the whole gram.
+    ///
+    /// Without any guesses, it matches everything.
+    pub fn regex(&self) -> String {
+        if self.positions.is_empty() {
+            return String::from("^(?s:.*)$");
+        }
+        let mut re = String::from("^");
+        for position in &self.positions {
+            match position {
+                Position::Known(c) => {
+                    re.push_str(&regex_syntax::escape(c.encode_utf8(&mut [0; 4])))
+                }
+                Position::Not(letters) if letters.is_empty() => re.push('.'),
+                Position::Not(letters) => {
+                    re.push_str("[^");
+                    for c in letters {
+                        re.push_str(&regex_syntax::escape(c.encode_utf8(&mut [0; 4])));
+                    }
+                    re.push(']');
+                }
+            }
+        }
+        re.push('$');
+        re
+    }
+}
+
+/// The marks a guess gets against an answer as long as it, as a number.
+///
+/// Each mark is a digit in base 3, the first letter least significant, of 0 for grey,
+/// 1 for yellow and 2 for green. Past 40 letters the number wraps around,
+/// so the marks on such long guesses may be mistaken for others.
+///
+/// Letters are marked green first, then yellow from the left while the answer has more of them.
+pub(crate) fn feedback(guess: &[char], answer: &[char]) -> u64 {
+    let mut code = 0u64;
+    let mut digit = 1u64;
+    for (i, (g, a)) in guess.iter().zip(answer).enumerate() {
+        let mark = if g == a {
+            2
+        } else {
+            // The letters of the answer not marked green, less those marked yellow to the left.
+            let unmatched = guess
+                .iter()
+                .zip(answer)
+                .filter(|&(other, a)| a == g && other != a)
+                .count();
+            let marked = guess[..i]
+                .iter()
+                .zip(answer)
+                .filter(|&(other, a)| other == g && other != a)
+                .count();
+            u64::from(marked < unmatched)
+        };
+        code = code.wrapping_add(mark * digit);
+        digit = digit.wrapping_mul(3);
+    }
+    code
+}
+
+/// Score a guess by how it splits the candidates, each with the letters of the answer and its weight.
+///
+/// Candidates of another length than the guess are told apart by their length alone.
+/// The buckets are cleared and reused, so scoring many guesses allocates them once.
+fn score(
+    guess: &[char],
+    candidates: &[(Vec<char>, f64)],
+    strategy: Strategy,
+    buckets: &mut HashMap<Option<u64>, (f64, usize)>,
+) -> f64 {
+    buckets.clear();
+    for (answer, weight) in candidates {
+        let marks = (answer.len() == guess.len()).then(|| feedback(guess, answer));
+        let (total, count) = buckets.entry(marks).or_default();
+        *total += weight;
+        *count += 1;
+    }
+
+    let total = buckets.values().map(|(weight, _)| weight).sum::<f64>();
+    if total <= 0.0 {
+        return 0.0;
+    }
+    // Marks all green find the answer, so they leave no candidates.
+    let solved = Some(3u64.wrapping_pow(guess.len() as u32).wrapping_sub(1));
+    let probabilities = buckets
+        .iter()
+        .map(|(marks, &(weight, count))| {
+            let left = if *marks == solved { 0 } else { count };
+            (weight / total, left)
+        })
+        .filter(|&(p, _)| p > 0.0);
+    match strategy {
+        Strategy::Information => probabilities.map(|(p, _)| p * (1.0 / p).log2()).sum(),
+        Strategy::Remaining => probabilities.map(|(p, count)| p * count as f64).sum(),
+    }
+}
+
+/// [Score](score) each guess against the candidates, in order until the budget is spent.
+///
+/// Each guess scored counts as a node visited.
+/// Without a budget, chunks of guesses are scored on each thread under the `parallel` feature.
+pub(crate) fn scores(
+    guesses: &[Vec<char>],
+    candidates: &[(Vec<char>, f64)],
+    strategy: Strategy,
+    budget: &Budget,
+) -> Searched<f64> {
+    #[cfg(feature = "parallel")]
+    if super::splittable(budget) {
+        use rayon::prelude::*;
+        let scores = guesses
+            .par_iter()
+            .map_init(HashMap::new, |buckets, guess| {
+                score(guess, candidates, strategy, buckets)
+            })
+            .collect();
+        return (scores, None);
+    }
+
+    let mut meter = Meter::new(budget.clone());
+    let mut buckets = HashMap::new();
+    let scores = guesses
+        .iter()
+        .map_while(|guess| {
+            meter
+                .visit()
+                .then(|| score(guess, candidates, strategy, &mut buckets))
+        })
+        .collect();
+    (scores, meter.limit())
+}
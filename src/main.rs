@@ -1,6 +1,11 @@
 mod cli;
 use clap::Parser;
-use grumpr::librarian::{Gram, Librarian, Library, Stats, query};
+use grumpr::librarian::{
+    Annotation, Gram, Librarian, Library, Metadata, Shelf, Stats,
+    binary::{BinaryLibrary, MappedLibrary},
+    pipeline::Pipeline,
+    query,
+};
 use itertools::Itertools;
 use std::{
     collections::HashMap,
@@ -24,16 +29,17 @@ fn try_main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
 
     let (library, cmd_i) = process_cmd_0(cli.cmd)?;
-    let mut librarian: Librarian = (&library).into();
+    let mut librarian = Librarian::new(library.as_ref());
     let cmd_n = process_cmd_i(&mut librarian, cmd_i)?;
     process_cmd_n(librarian, cmd_n)?;
 
     Ok(())
 }
 
+#[allow(clippy::type_complexity)]
 fn process_cmd_0(
     cmd: cli::Cmd0,
-) -> Result<(Library, Option<cli::CmdI>), Box<dyn std::error::Error>> {
+) -> Result<(Box<dyn Shelf>, Option<cli::CmdI>), Box<dyn std::error::Error>> {
     use cli::Cmd0;
 
     Ok(match cmd {
@@ -48,6 +54,9 @@ fn process_cmd_i(
 ) -> Result<Option<cli::CmdN>, Box<dyn std::error::Error>> {
     use cli::CmdI;
 
+    // Results saved by `branch`, most recent last
+    let mut branches = Vec::new();
+
     while let Some(cmd) = cmd_i {
         cmd_i = match cmd {
             CmdI::Filter(opts) => {
@@ -72,20 +81,11 @@ fn process_cmd_i(
                     };
                 }
                 if let Some(top) = opts.inner.top {
-                    let seed = librarian
-                        .iter()
-                        .flat_map(|gram| gram.seeds())
-                        .sorted_by(|lhs, rhs| rhs.count.cmp(&lhs.count))
-                        .dedup_by(|lhs, rhs| lhs.index.eq(&rhs.index))
-                        .nth(top);
-                    if let Some(seed) = seed {
-                        let count = seed.count;
-                        *librarian = if opts.inner.negate {
-                            librarian.filter(|seed| seed.count <= count)
-                        } else {
-                            librarian.filter(|seed| seed.count > count)
-                        };
-                    }
+                    *librarian = if opts.inner.negate {
+                        librarian.without_top_seeds(top)
+                    } else {
+                        librarian.top_seeds(top)
+                    };
                 }
 
                 opts.next
@@ -96,6 +96,17 @@ fn process_cmd_i(
                 *librarian = librarian.search(&query).unwrap();
                 opts.next
             }
+            CmdI::Cross(opts) => {
+                let query =
+                    query::Pattern::new(&opts.inner.pattern).depth(opts.inner.depth.depth - 1);
+                *librarian = librarian.pattern(&query)?;
+                opts.next
+            }
+            CmdI::Complete(opts) => {
+                let query = query::Prefix::new(&opts.inner.prefix);
+                *librarian = librarian.complete(&query)?;
+                opts.next
+            }
             CmdI::Anna(opts) => {
                 let query = query::Anagram::new(&opts.inner.pattern)
                     .partial(opts.inner.partial)
@@ -125,6 +136,74 @@ fn process_cmd_i(
 
                 opts.next
             }
+            CmdI::Only(opts) => {
+                let query = query::Only::new(&opts.inner.letters)
+                    .required(&opts.inner.required)
+                    .min_length(opts.inner.min_length)
+                    .depth(opts.inner.depth.depth - 1);
+                *librarian = librarian.only(&query)?;
+                opts.next
+            }
+            CmdI::Letters(opts) => {
+                let mut query = query::Letters::new()
+                    .exclude(&opts.inner.exclude)
+                    .distinct(opts.inner.unique)
+                    .depth(opts.inner.depth.depth - 1);
+                for count in &opts.inner.count {
+                    let (letter, range) = query::Letters::parse_count(count).ok_or_else(|| {
+                        format!("Invalid count `{count}`, expected a letter and how many such as `s<=2`")
+                    })?;
+                    query = query.count(letter, range);
+                }
+                *librarian = librarian.letters(&query)?;
+                opts.next
+            }
+            CmdI::Wordle(opts) => {
+                let mut query = query::Wordle::new();
+                for guess in &opts.inner.guesses {
+                    let (word, marks) = query::Wordle::parse_guess(guess).ok_or_else(|| {
+                        format!("Invalid guess `{guess}`, expected a word and its marks such as `crane:gy..g`")
+                    })?;
+                    query = query.guess(word, marks);
+                }
+                *librarian = librarian.wordle(&query)?;
+                opts.next
+            }
+            CmdI::Query(opts) => {
+                let input = &opts.inner.query;
+                let pipeline = Pipeline::parse(input).map_err(|err| {
+                    // Point at the error beneath the query
+                    let column = input[..err.span.start].chars().count();
+                    let width = input[err.span.clone()].chars().count().max(1);
+                    format!(
+                        "{err}\n  {input}\n  {}{}",
+                        " ".repeat(column),
+                        "^".repeat(width)
+                    )
+                })?;
+                *librarian = librarian.run(&pipeline, &query::Budget::new())?;
+                opts.next
+            }
+            CmdI::Branch(opts) => {
+                branches.push(librarian.clone());
+                opts.next
+            }
+            CmdI::Swap(opts) => {
+                let branch = branches.last_mut().ok_or("No branch to swap with")?;
+                std::mem::swap(librarian, branch);
+                opts.next
+            }
+            CmdI::Merge(opts) => {
+                use cli::SetOp;
+                let branch = branches.pop().ok_or("No branch to merge with")?;
+                *librarian = match opts.inner.op {
+                    SetOp::Union => branch.union(librarian),
+                    SetOp::Intersection => branch.intersection(librarian),
+                    SetOp::Difference => branch.difference(librarian),
+                    SetOp::SymmetricDifference => branch.symmetric_difference(librarian),
+                };
+                opts.next
+            }
             CmdI::Final(final_cmd) => {
                 return Ok(Some(final_cmd));
             }
@@ -150,9 +229,13 @@ fn process_cmd_n(
             index: true,
             count: false,
             frequency: true,
+            notes: librarian.is_annotated(),
+            ..Default::default()
         })));
     }
 
+    warn_truncated("search", librarian.truncated());
+
     while let Some(cmd) = cmd_n {
         cmd_n = match cmd {
             CmdN::Show(opts) => {
@@ -166,18 +249,17 @@ fn process_cmd_n(
                     0
                 };
 
-                // TODO: Expose to cli how to sort the results
-                // Also limit the number of results
-                let grams = librarian
-                    .iter()
-                    .enumerate()
-                    .sorted_by(|(_, lhs), (_, rhs)| lhs.cmp_by_count_mean(rhs))
-                    .rev();
+                let ranked = librarian.top_k(
+                    opts.inner.limit.unwrap_or(librarian.len()),
+                    opts.inner.sort.into(),
+                );
+                let grams = ranked.iter_annotated().enumerate();
 
                 // TODO: Format the results nicely in a table with padding
-                for (index, gram) in grams {
+                for (index, (gram, note)) in grams {
                     let show_gram = ShowGram {
                         gram,
+                        note,
                         total,
                         rank: index,
                         opts: &opts.inner,
@@ -187,8 +269,39 @@ fn process_cmd_n(
 
                 opts.next
             }
-            CmdN::Write(opts) => {
-                todo!("Write librarian to file {:#?}", opts);
+            CmdN::Write(mut opts) => {
+                let format = match opts.inner.format {
+                    Some(format) => format,
+                    None if opts.inner.file.is_local() => {
+                        library_format(opts.inner.file.path().extension())?
+                    }
+                    None => return Err("Unable to determine library format for output".into()),
+                };
+                // Sequences are written as a single root, the library is flattened
+                let library = librarian
+                    .iter()
+                    .map(|gram| (gram.root().into_owned(), gram.count_mean()))
+                    .collect::<Library>()
+                    .with_metadata(librarian.metadata().into_owned());
+                library_write(&library, &mut opts.inner.file, format, !opts.inner.no_index)?;
+                opts.inner.file.finish()?;
+
+                opts.next
+            }
+            CmdN::Guess(opts) => {
+                let query = query::Guesses::new()
+                    .strategy(opts.inner.strategy.into())
+                    .weighted(opts.inner.weighted)
+                    .any(opts.inner.any)
+                    .top(opts.inner.top);
+                let mut stdout = std::io::stdout().lock();
+                let (ranked, limit) = librarian.rank_guesses(&query);
+                warn_truncated("ranking", limit);
+                for (gram, score) in ranked {
+                    writeln!(stdout, "{}\t{score:.3}", gram.root())?;
+                }
+
+                opts.next
             }
             CmdN::Stats(opts) => {
                 let stats = librarian.stats();
@@ -214,40 +327,54 @@ fn process_cmd_n(
     Ok(())
 }
 
-fn get_library(opts: Option<cli::OptsLibrary>) -> Result<Library, Box<dyn std::error::Error>> {
-    use cli::{BuiltinOrFile, LibraryFormat};
+/// Warn that a search ran out of its budget, so only the results found before then are given.
+fn warn_truncated(what: &str, limit: Option<query::Limit>) {
+    if let Some(limit) = limit {
+        eprintln!(
+            "Warning: The {what} stopped early at its {limit} limit, so some results may be missing"
+        );
+    }
+}
+
+fn get_library(
+    opts: Option<cli::OptsLibrary>,
+) -> Result<Box<dyn Shelf>, Box<dyn std::error::Error>> {
+    use cli::{BuiltinFile, BuiltinOrFile, LibraryFormat};
     let mut opts = opts.unwrap_or_default();
 
     if opts.build {
-        if matches!(opts.file, BuiltinOrFile::Builtin(_)) {
+        let BuiltinOrFile::File(source) = &opts.file else {
             return Err("Built-in libraries cannot be built".into());
-        }
+        };
 
+        let source = source.path().display().to_string();
         let file = opts.file.reader();
-        library_build(file, opts.threshold, opts.ignore_case)
+        let mut library = library_build(file, opts.threshold, opts.ignore_case)?;
+        let metadata = library.metadata_mut();
+        metadata.sources.push(source);
+        metadata.name = opts.name;
+        metadata.language = opts.language;
+        Ok(Box::new(library))
     } else {
         let format = match &opts.file {
-            BuiltinOrFile::Builtin(_) => {
-                // All builtins are TSV
-                // This catches where the user doesn't specify a format
-                // but they specify a built-in library
-                matches!(opts.format, None | Some(LibraryFormat::TSV))
-                    .then_some(LibraryFormat::TSV)
-                    .ok_or(<Box<dyn std::error::Error>>::from(
-                        "Built-in libraries must be in TSV format",
-                    ))?
-                // TODO: Look at embedding the built-in in a faster format to read /
-                // mmap it directly as a Library?
+            BuiltinOrFile::Builtin(builtin) => {
+                // All builtins are embedded in the binary format and read in place
+                // This catches where the user specifies another format for a built-in library
+                if !matches!(opts.format, None | Some(LibraryFormat::Binary)) {
+                    return Err("Built-in libraries are in the binary format".into());
+                }
+                return Ok(Box::new(BinaryLibrary::new(builtin.bytes())?));
             }
             BuiltinOrFile::File(file) if file.is_local() => {
                 // If the file is local, we can determine the format from the file extension
-                match file.path().extension().and_then(|s| s.to_str()) {
-                    Some("tsv") => LibraryFormat::TSV,
-                    Some("csv") => LibraryFormat::CSV,
-                    _ => {
-                        return Err("Unable to determine library format from file extension".into());
-                    }
+                let format = opts
+                    .format
+                    .map_or_else(|| library_format(file.path().extension()), Ok)?;
+                if format == LibraryFormat::Binary {
+                    // Search the file in place rather than reading it all in
+                    return Ok(Box::new(MappedLibrary::open(file.path().path())?));
                 }
+                format
             }
             // If the file is not local, we cannot determine the format
             // and we require the user to specify it
@@ -256,20 +383,37 @@ fn get_library(opts: Option<cli::OptsLibrary>) -> Result<Library, Box<dyn std::e
             ))?,
         };
 
-        library_parse(opts.file.reader(), format)
+        Ok(Box::new(library_parse(opts.file.reader(), format)?))
     }
 }
 
+fn library_format(
+    extension: Option<&std::ffi::OsStr>,
+) -> Result<cli::LibraryFormat, Box<dyn std::error::Error>> {
+    use cli::LibraryFormat;
+
+    Ok(match extension.and_then(|s| s.to_str()) {
+        Some("tsv") => LibraryFormat::TSV,
+        Some("csv") => LibraryFormat::CSV,
+        Some("grumpr") => LibraryFormat::Binary,
+        _ => {
+            return Err("Unable to determine library format from file extension".into());
+        }
+    })
+}
+
 fn library_build(
     file: impl std::io::BufRead,
     threshold: u64,
     ignore_case: bool,
 ) -> Result<Library, Box<dyn std::error::Error>> {
     let mut counter = HashMap::<String, u64>::new();
+    let mut tokens = 0;
 
     for line in file.lines() {
         let line = line?;
         for word in line.unicode_words() {
+            tokens += 1;
             let word = if ignore_case {
                 word.to_lowercase()
             } else {
@@ -279,14 +423,20 @@ fn library_build(
         }
     }
 
-    Ok(counter
+    let library = counter
         .into_iter()
         .filter(|(_, count)| *count >= threshold)
-        .collect())
+        .collect::<Library>();
+    Ok(library.with_metadata(Metadata {
+        tokens: Some(tokens),
+        threshold: Some(threshold),
+        case_folded: Some(ignore_case),
+        ..Default::default()
+    }))
 }
 
 fn library_parse(
-    file: impl std::io::Read,
+    mut file: impl std::io::BufRead,
     format: cli::LibraryFormat,
 ) -> Result<Library, Box<dyn std::error::Error>> {
     use cli::LibraryFormat;
@@ -299,6 +449,7 @@ fn library_parse(
 
     Ok(match format {
         LibraryFormat::TSV => {
+            let (metadata, file) = Metadata::read_comments(file)?;
             let mut parser = csv::ReaderBuilder::new()
                 .has_headers(false)
                 .delimiter(b'\t')
@@ -307,8 +458,10 @@ fn library_parse(
                 .deserialize()
                 .map(|res: Result<GramRecord, _>| res.map(|rec| (rec.root, rec.count)))
                 .collect::<csv::Result<Library>>()?
+                .with_metadata(metadata)
         }
         LibraryFormat::CSV => {
+            let (metadata, file) = Metadata::read_comments(file)?;
             let mut parser = csv::ReaderBuilder::new()
                 .has_headers(false)
                 .from_reader(file);
@@ -316,10 +469,44 @@ fn library_parse(
                 .deserialize()
                 .map(|res: Result<GramRecord, _>| res.map(|rec| (rec.root, rec.count)))
                 .collect::<csv::Result<Library>>()?
+                .with_metadata(metadata)
+        }
+        LibraryFormat::Binary => {
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            BinaryLibrary::new(&bytes)?.into()
         }
     })
 }
 
+fn library_write(
+    library: &Library,
+    mut file: impl std::io::Write,
+    format: cli::LibraryFormat,
+    index: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use cli::LibraryFormat;
+
+    let delimiter = match format {
+        LibraryFormat::TSV => b'\t',
+        LibraryFormat::CSV => b',',
+        LibraryFormat::Binary => {
+            library.write_binary(std::io::BufWriter::new(file), index)?;
+            return Ok(());
+        }
+    };
+    library.metadata().write_comments(&mut file)?;
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .delimiter(delimiter)
+        .from_writer(file);
+    for seed in library.iter() {
+        writer.serialize((&seed.root, seed.count))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
 struct ShowHeader<'a> {
     opts: &'a cli::OptsShow,
@@ -340,6 +527,9 @@ impl<'a> std::fmt::Display for ShowHeader<'a> {
         if self.opts.count {
             write!(f, "Count\t")?;
         }
+        if self.opts.notes {
+            write!(f, "Notes\t")?;
+        }
         Ok(())
     }
 }
@@ -347,6 +537,7 @@ impl<'a> std::fmt::Display for ShowHeader<'a> {
 #[derive(Debug, Clone)]
 struct ShowGram<'a, 'l> {
     gram: Gram<'l>,
+    note: &'a Annotation,
     rank: usize,
     total: u64,
     opts: &'a cli::OptsShow,
@@ -369,6 +560,9 @@ impl<'a, 'l> std::fmt::Display for ShowGram<'a, 'l> {
                 if self.opts.count {
                     write!(f, "{}\t", seed.count)?;
                 }
+                if self.opts.notes {
+                    write!(f, "{}\t", self.note)?;
+                }
             }
             Gram::Sequence(seeds) => {
                 write!(f, "{}", seeds.iter().map(|w| &w.root).join(" "))?;
@@ -395,6 +589,9 @@ impl<'a, 'l> std::fmt::Display for ShowGram<'a, 'l> {
                         seeds.iter().map(|s| s.count).sum::<u64>() / seeds.len() as u64
                     )?;
                 }
+                if self.opts.notes {
+                    write!(f, "\t{}", self.note)?;
+                }
             }
         }
         Ok(())
@@ -428,6 +625,26 @@ impl std::fmt::Display for ShowStats {
             self.0.chars_ngrams, self.0.occurrences.chars_ngrams
         )?;
 
+        let metadata = &self.0.metadata;
+        if let Some(name) = &metadata.name {
+            writeln!(f, "Name        \t{name}")?;
+        }
+        for source in &metadata.sources {
+            writeln!(f, "Source      \t{source}")?;
+        }
+        if let Some(tokens) = metadata.tokens {
+            writeln!(f, "Tokens      \t{tokens}")?;
+        }
+        if let Some(threshold) = metadata.threshold {
+            writeln!(f, "Threshold   \t{threshold}")?;
+        }
+        if let Some(case_folded) = metadata.case_folded {
+            writeln!(f, "Case Folded \t{case_folded}")?;
+        }
+        if let Some(language) = &metadata.language {
+            writeln!(f, "Language    \t{language}")?;
+        }
+
         Ok(())
     }
 }
@@ -1,22 +1,34 @@
 use super::{Key, Trie};
+use std::ops::Bound;
 
+/// Iterator over the immediate children of a node, in ascending byte order.
 #[derive(Debug, Clone)]
 pub struct Bytes<'a, K: Key, V> {
-    base: &'a Trie<K, V>,
-    bottom: u8,
-    top: u8,
+    children: std::slice::Iter<'a, (u8, Trie<K, V>)>,
 }
-const _: () = assert!(
-    super::CHILDREN <= (u8::MAX as usize + 1),
-    "Trie children must fit within a u8"
-);
 
 impl<'a, K: Key, V> Bytes<'a, K, V> {
     pub fn new(trie: &'a Trie<K, V>) -> Self {
         Self {
-            base: trie,
-            bottom: 0u8.wrapping_sub(1),
-            top: trie.children.len() as u8,
+            children: trie.children.iter(),
+        }
+    }
+}
+
+impl<'a, K: Key, V> Bytes<'a, K, V> {
+    /// The children of `trie` with a byte greater than `byte`.
+    pub fn after(trie: &'a Trie<K, V>, byte: u8) -> Self {
+        let start = trie.children.partition_point(|(b, _)| *b <= byte);
+        Self {
+            children: trie.children[start..].iter(),
+        }
+    }
+
+    /// The children of `trie` with a byte less than `byte`.
+    pub fn before(trie: &'a Trie<K, V>, byte: u8) -> Self {
+        let end = trie.children.partition_point(|(b, _)| *b < byte);
+        Self {
+            children: trie.children[..end].iter(),
         }
     }
 }
@@ -25,31 +37,200 @@ impl<'a, K: Key, V> Iterator for Bytes<'a, K, V> {
     type Item = (u8, &'a Trie<K, V>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        'start: loop {
-            while self.top < self.base.children.len() as u8 {
-                if let Some(ref child) = self.base.children[self.bottom as usize]
-                    .as_ref()
-                    .unwrap()
-                    .children[self.top as usize]
-                {
-                    self.top += 1;
-                    return Some(((self.bottom << 4) + (self.top - 1), child));
+        self.children.next().map(|(byte, child)| (*byte, child))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.children.size_hint()
+    }
+}
+
+impl<K: Key, V> DoubleEndedIterator for Bytes<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.children
+            .next_back()
+            .map(|(byte, child)| (*byte, child))
+    }
+}
+
+impl<K: Key, V> ExactSizeIterator for Bytes<'_, K, V> {}
+
+/// A node whose value is yet to be yielded from the back, its remaining children and the length of its key.
+type BackEntry<'a, K, V> = (&'a Trie<K, V>, Bytes<'a, K, V>, usize);
+
+/// Depth-first walk over the `(key, value)` pairs beneath a node, yielding the full key bytes.
+/// Keys are yielded in lexicographic byte order, shorter keys before their extensions,
+/// and can be walked from either end.
+#[derive(Debug, Clone)]
+pub struct Iter<'a, K: Key, V> {
+    /// Children left to visit from the front, with the length of their parent's key.
+    front: Vec<(Bytes<'a, K, V>, usize)>,
+    front_key: Vec<u8>,
+    /// The next node from the front, whose value is yet to be yielded.
+    front_node: Option<&'a Trie<K, V>>,
+    /// Nodes whose value is yet to be yielded from the back, once their remaining children have been.
+    back: Vec<BackEntry<'a, K, V>>,
+    back_key: Vec<u8>,
+    /// Keys outside of these bounds are either out of range or already yielded from the other end.
+    lower: Bound<Vec<u8>>,
+    upper: Bound<Vec<u8>>,
+}
+
+impl<'a, K: Key, V> Iter<'a, K, V> {
+    /// Walk the subtree of `node`, where `prefix` is the key leading to `node`.
+    /// If `node` is `None`, the iterator is empty.
+    pub fn new(node: Option<&'a Trie<K, V>>, prefix: Vec<u8>) -> Self {
+        Self {
+            front: Vec::new(),
+            front_key: prefix.clone(),
+            front_node: node,
+            back: node
+                .map(|node| (node, node.bytes(), prefix.len()))
+                .into_iter()
+                .collect(),
+            back_key: prefix,
+            lower: Bound::Unbounded,
+            upper: Bound::Unbounded,
+        }
+    }
+
+    /// Walk the keys of `trie` within the given bounds.
+    pub fn range(trie: &'a Trie<K, V>, lower: Bound<Vec<u8>>, upper: Bound<Vec<u8>>) -> Self {
+        let mut iter = Self::new(None, Vec::new());
+        iter.seek_front(trie, &lower);
+        iter.seek_back(trie, &upper);
+        iter.lower = lower;
+        iter.upper = upper;
+        iter
+    }
+
+    /// Position the front on the first key at or above `lower`.
+    fn seek_front(&mut self, trie: &'a Trie<K, V>, lower: &Bound<Vec<u8>>) {
+        let (Bound::Included(lower) | Bound::Excluded(lower)) = lower else {
+            self.front_node = Some(trie);
+            return;
+        };
+        let mut node = trie;
+        for (depth, &byte) in lower.iter().enumerate() {
+            // Children before the byte are below the bound, the one equal to it continues the path.
+            self.front.push((Bytes::after(node, byte), depth));
+            match node.child(byte) {
+                Some(child) => node = child,
+                None => return,
+            }
+            self.front_key.push(byte);
+        }
+        self.front_node = Some(node);
+    }
+
+    /// Position the back on the last key at or below `upper`.
+    fn seek_back(&mut self, trie: &'a Trie<K, V>, upper: &Bound<Vec<u8>>) {
+        let (Bound::Included(upper) | Bound::Excluded(upper)) = upper else {
+            self.back.push((trie, trie.bytes(), 0));
+            return;
+        };
+        let mut node = trie;
+        for (depth, &byte) in upper.iter().enumerate() {
+            // The node itself and the children before the byte are below the bound.
+            self.back.push((node, Bytes::before(node, byte), depth));
+            match node.child(byte) {
+                Some(child) => node = child,
+                None => return,
+            }
+            self.back_key.push(byte);
+        }
+        // Every child extends the bound, so is above it.
+        self.back
+            .push((node, Bytes::before(node, u8::MIN), upper.len()));
+    }
+
+    fn above_lower(&self, key: &[u8]) -> bool {
+        match &self.lower {
+            Bound::Included(lower) => key >= lower.as_slice(),
+            Bound::Excluded(lower) => key > lower.as_slice(),
+            Bound::Unbounded => true,
+        }
+    }
+
+    fn below_upper(&self, key: &[u8]) -> bool {
+        match &self.upper {
+            Bound::Included(upper) => key <= upper.as_slice(),
+            Bound::Excluded(upper) => key < upper.as_slice(),
+            Bound::Unbounded => true,
+        }
+    }
+
+    fn finish(&mut self) {
+        self.front.clear();
+        self.front_node = None;
+        self.back.clear();
+    }
+}
+
+impl<'a, K: Key, V> Iterator for Iter<'a, K, V> {
+    type Item = (Vec<u8>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(node) = self.front_node.take() {
+                self.front.push((node.bytes(), self.front_key.len()));
+                let Some(value) = node.value.as_ref() else {
+                    continue;
+                };
+                if !self.below_upper(&self.front_key) {
+                    self.finish();
+                    return None;
+                }
+                if self.above_lower(&self.front_key) {
+                    self.lower = Bound::Excluded(self.front_key.clone());
+                    return Some((self.front_key.clone(), value));
+                }
+            } else {
+                let (children, len) = self.front.last_mut()?;
+                if let Some((byte, child)) = children.next() {
+                    self.front_key.truncate(*len);
+                    self.front_key.push(byte);
+                    self.front_node = Some(child);
                 } else {
-                    self.top += 1;
+                    self.front.pop();
                 }
             }
-            while self.bottom != (self.base.children.len() - 1) as u8 {
-                self.bottom = self.bottom.wrapping_add(1);
-                if self.base.children[self.bottom as usize].is_some() {
-                    self.top = 0;
-                    continue 'start;
-                }
+        }
+    }
+}
+
+impl<K: Key, V> DoubleEndedIterator for Iter<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, children, len) = self.back.last_mut()?;
+            if let Some((byte, child)) = children.next_back() {
+                self.back_key.truncate(*len);
+                self.back_key.push(byte);
+                let len = *len + 1;
+                self.back.push((child, child.bytes(), len));
+                continue;
+            }
+
+            let (node, len) = (*node, *len);
+            self.back.pop();
+            let Some(value) = node.value.as_ref() else {
+                continue;
+            };
+            self.back_key.truncate(len);
+            if !self.above_lower(&self.back_key) {
+                self.finish();
+                return None;
+            }
+            if self.below_upper(&self.back_key) {
+                self.upper = Bound::Excluded(self.back_key.clone());
+                return Some((self.back_key.clone(), value));
             }
-            return None;
         }
     }
 }
 
+impl<K: Key, V> std::iter::FusedIterator for Iter<'_, K, V> {}
+
 impl<K: Key, V> FromIterator<(K, V)> for Trie<K, V> {
     fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
         let mut trie = Trie::new();
@@ -8,12 +8,12 @@ pub trait Key {
     fn as_bytes(&self) -> impl IntoIterator<Item = u8> + '_;
 }
 
-const CHILDREN: usize = 16;
-
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Trie<K: Key + ?Sized, V> {
     pub value: Option<V>,
-    // TODO: Option the whole array?
-    children: [Option<Box<Trie<K, V>>>; CHILDREN],
+    /// Children sorted by their byte.
+    /// Most nodes only have a handful of children, so they are stored sparsely
+    /// in an exactly sized slice rather than a full array per byte.
+    children: Box<[(u8, Trie<K, V>)]>,
     _marker: std::marker::PhantomData<K>,
 }
@@ -1,5 +1,8 @@
-use super::{Key, Trie, iter::Bytes, keys::KeyNibbles};
-use std::borrow::Borrow;
+use super::{
+    Key, Trie,
+    iter::{Bytes, Iter},
+};
+use std::{borrow::Borrow, ops::RangeBounds};
 
 impl<K: Key, V> Default for Trie<K, V> {
     fn default() -> Self {
@@ -23,46 +26,178 @@ impl<K: Key, V> Trie<K, V> {
         Q: Key + ?Sized,
     {
         let mut current_node = self;
-        let mut nibbles = key.as_nibbles().into_iter();
-        while let Some(index) = nibbles.next() {
-            if let Some(ref mut next) = current_node.children[index as usize] {
-                current_node = next;
-            } else {
-                current_node.children[index as usize] =
-                    Some(Box::new(Self::insert_fast(nibbles, value)));
-                return None;
-            }
+        for byte in key.as_bytes() {
+            current_node = current_node.child_or_insert(byte);
         }
-
         current_node.value.replace(value)
     }
 
-    #[must_use]
-    fn insert_fast(nibbles: impl Iterator<Item = u8>, value: V) -> Self {
-        let mut top = Trie::new();
-        let mut trie = &mut top;
-        for index in nibbles {
-            trie.children[index as usize] = Some(Box::new(Trie::new()));
-            trie = trie.children[index as usize].as_deref_mut().unwrap();
-        }
-        trie.value = Some(value);
-        top
+    /// Returns the child for `byte`, inserting an empty node if there is none.
+    fn child_or_insert(&mut self, byte: u8) -> &mut Self {
+        let index = match self.search(byte) {
+            Ok(index) => index,
+            Err(index) => {
+                // Rebuild the slice so it never holds spare capacity.
+                // A node has at most one child per byte, so this costs less than the
+                // spare capacity a growable list would keep.
+                let mut old = std::mem::take(&mut self.children).into_vec().into_iter();
+                let mut children = Vec::with_capacity(old.len() + 1);
+                children.extend(old.by_ref().take(index));
+                children.push((byte, Trie::new()));
+                children.extend(old);
+                self.children = children.into_boxed_slice();
+                index
+            }
+        };
+        &mut self.children[index].1
+    }
+
+    /// Binary search the children for `byte`.
+    fn search(&self, byte: u8) -> Result<usize, usize> {
+        self.children.binary_search_by_key(&byte, |(b, _)| *b)
+    }
+
+    pub(super) fn child(&self, byte: u8) -> Option<&Self> {
+        self.search(byte).ok().map(|index| &self.children[index].1)
     }
 
     #[must_use]
     pub fn _get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Key + ?Sized,
+    {
+        self.node(key)?.value.as_ref()
+    }
+
+    /// Returns the node reached by following `key`, if any.
+    #[must_use]
+    pub fn node<Q>(&self, key: &Q) -> Option<&Self>
     where
         K: Borrow<Q>,
         Q: Key + ?Sized,
     {
         let mut current_node = self;
-        for index in key.as_nibbles() {
-            current_node = current_node.children[index as usize].as_deref()?;
+        for byte in key.as_bytes() {
+            current_node = current_node.child(byte)?;
         }
-        current_node.value.as_ref()
+        Some(current_node)
+    }
+
+    /// Returns an iterator over every `(key, value)` pair whose key starts with `prefix`.
+    /// Pairs are yielded in lexicographic order of their keys.
+    pub fn prefix<Q>(&self, prefix: &Q) -> Iter<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Key + ?Sized,
+    {
+        Iter::new(self.node(prefix), prefix.as_bytes().into_iter().collect())
+    }
+
+    /// Returns an iterator over every `(key, value)` pair whose key lies within `range`.
+    /// Pairs are yielded in lexicographic order of their keys.
+    pub fn range<'q, Q>(&self, range: impl RangeBounds<&'q Q>) -> Iter<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Key + ?Sized + 'q,
+    {
+        let bytes = |key: &&Q| key.as_bytes().into_iter().collect::<Vec<_>>();
+        Iter::range(
+            self,
+            range.start_bound().map(bytes),
+            range.end_bound().map(bytes),
+        )
     }
 
     pub fn bytes(&self) -> Bytes<'_, K, V> {
         Bytes::new(self)
     }
 }
+
+impl<K: Key, V> Trie<K, V> {
+    /// Returns `true` if the node holds no value and has no children.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.value.is_none() && self.children.is_empty()
+    }
+
+    /// Removes the value stored at `key`, pruning any nodes left empty.
+    #[allow(dead_code)]
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Key + ?Sized,
+    {
+        let key = key.as_bytes().into_iter().collect::<Vec<_>>();
+        self.remove_bytes(&key)
+    }
+
+    fn remove_bytes(&mut self, key: &[u8]) -> Option<V> {
+        let Some((&byte, rest)) = key.split_first() else {
+            return self.value.take();
+        };
+        let index = self.search(byte).ok()?;
+        let child = &mut self.children[index].1;
+        let value = child.remove_bytes(rest);
+        if child.is_empty() {
+            self.remove_child(index);
+        }
+        value
+    }
+
+    /// Removes every value whose key starts with `prefix`, pruning any nodes left empty.
+    #[allow(dead_code)]
+    pub fn clear_prefix<Q>(&mut self, prefix: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Key + ?Sized,
+    {
+        let prefix = prefix.as_bytes().into_iter().collect::<Vec<_>>();
+        self.clear_bytes(&prefix);
+    }
+
+    fn clear_bytes(&mut self, prefix: &[u8]) {
+        let Some((&byte, rest)) = prefix.split_first() else {
+            *self = Self::default();
+            return;
+        };
+        if let Ok(index) = self.search(byte) {
+            let child = &mut self.children[index].1;
+            child.clear_bytes(rest);
+            if child.is_empty() {
+                self.remove_child(index);
+            }
+        }
+    }
+
+    /// Retains only the values for which `f` returns `true`, pruning any nodes left empty.
+    /// The predicate is given the key bytes and the value, visited in lexicographic order.
+    pub fn retain(&mut self, mut f: impl FnMut(&[u8], &mut V) -> bool) {
+        self.retain_bytes(&mut Vec::new(), &mut f);
+    }
+
+    fn retain_bytes(&mut self, key: &mut Vec<u8>, f: &mut impl FnMut(&[u8], &mut V) -> bool) {
+        if let Some(value) = self.value.as_mut()
+            && !f(key, value)
+        {
+            self.value = None;
+        }
+
+        for (byte, child) in self.children.iter_mut() {
+            key.push(*byte);
+            child.retain_bytes(key, f);
+            key.pop();
+        }
+        if self.children.iter().any(|(_, child)| child.is_empty()) {
+            let mut children = std::mem::take(&mut self.children).into_vec();
+            children.retain(|(_, child)| !child.is_empty());
+            self.children = children.into_boxed_slice();
+        }
+    }
+
+    fn remove_child(&mut self, index: usize) {
+        let mut children = std::mem::take(&mut self.children).into_vec();
+        children.remove(index);
+        self.children = children.into_boxed_slice();
+    }
+}
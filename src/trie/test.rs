@@ -1,4 +1,5 @@
 use itertools::Itertools;
+use std::ops::Bound;
 
 use super::*;
 
@@ -60,3 +61,172 @@ fn iter_bytes() {
 
     assert_eq!(found_bytes, expected_bytes);
 }
+
+#[test]
+fn prefix() {
+    let mut trie: Trie<String, _> = Trie::new();
+    let keys = ["car", "cat", "cattle", "ca", "dog", "c"];
+
+    for (value, &key) in keys.iter().enumerate() {
+        trie.insert(key, value);
+    }
+
+    let found = trie
+        .prefix("ca")
+        .map(|(key, &value)| (String::from_utf8(key).unwrap(), value))
+        .collect_vec();
+    assert_eq!(
+        found,
+        vec![
+            ("ca".to_owned(), 3),
+            ("car".to_owned(), 0),
+            ("cat".to_owned(), 1),
+            ("cattle".to_owned(), 2),
+        ]
+    );
+
+    assert_eq!(trie.prefix("").count(), keys.len());
+    assert_eq!(trie.prefix("cow").count(), 0);
+}
+
+#[test]
+fn remove() {
+    let mut trie: Trie<String, _> = Trie::new();
+    let keys = ["abc", "abcd", "abd"];
+
+    for (value, &key) in keys.iter().enumerate() {
+        trie.insert(key, value);
+    }
+
+    assert_eq!(trie.remove("ab"), None);
+    assert_eq!(trie.remove("abc"), Some(0));
+    assert_eq!(trie.remove("abc"), None);
+    assert_eq!(trie._get("abcd"), Some(&1));
+    assert!(trie.node("abc").is_some(), "Node still leads to abcd");
+
+    assert_eq!(trie.remove("abcd"), Some(1));
+    assert!(trie.node("abc").is_none(), "Empty branch is pruned");
+
+    assert_eq!(trie.remove("abd"), Some(2));
+    assert!(trie.is_empty());
+}
+
+#[test]
+fn clear_prefix() {
+    let mut trie: Trie<String, _> = Trie::new();
+    let keys = ["car", "cat", "cattle", "dog"];
+
+    for (value, &key) in keys.iter().enumerate() {
+        trie.insert(key, value);
+    }
+
+    trie.clear_prefix("cat");
+    assert_eq!(trie._get("car"), Some(&0));
+    assert_eq!(trie.prefix("cat").count(), 0);
+    assert!(trie.node("cat").is_none());
+    assert_eq!(trie.node("ca").unwrap().bytes().len(), 1);
+
+    trie.clear_prefix("ca");
+    assert!(trie.node("c").is_none(), "Empty branch is pruned");
+
+    trie.clear_prefix("");
+    assert!(trie.is_empty());
+}
+
+#[test]
+fn retain() {
+    let mut trie: Trie<String, _> = Trie::new();
+    let keys = ["car", "cat", "cattle", "dog"];
+
+    for (value, &key) in keys.iter().enumerate() {
+        trie.insert(key, value);
+    }
+
+    let mut visited = Vec::new();
+    trie.retain(|key, value| {
+        visited.push(String::from_utf8(key.to_vec()).unwrap());
+        *value % 2 == 1
+    });
+    assert_eq!(visited, ["car", "cat", "cattle", "dog"]);
+
+    let remaining = trie.prefix("").map(|(_, &value)| value).collect_vec();
+    assert_eq!(remaining, [1, 3]);
+    assert!(trie.node("car").is_none());
+    assert!(trie.node("catt").is_none());
+}
+
+#[test]
+fn iter_bytes_ordered() {
+    let mut trie: Trie<String, _> = Trie::new();
+    for (value, key) in ["zeta", "alpha", "mu", "beta"].into_iter().enumerate() {
+        trie.insert(key, value);
+    }
+
+    let found_bytes = trie.bytes().map(|(byte, _)| byte).collect_vec();
+    assert_eq!(found_bytes, b"abmz");
+    assert_eq!(trie.bytes().len(), 4);
+}
+
+#[test]
+fn iter_ordered() {
+    let mut trie: Trie<String, _> = Trie::new();
+    let keys = ["cattle", "dog", "c", "car", "ca", "cat"];
+    for (value, &key) in keys.iter().enumerate() {
+        trie.insert(key, value);
+    }
+
+    let as_str = |(key, _): (Vec<u8>, _)| String::from_utf8(key).unwrap();
+    let sorted = keys.iter().copied().sorted().collect_vec();
+    assert_eq!(trie.prefix("").map(as_str).collect_vec(), sorted);
+    assert_eq!(
+        trie.prefix("").rev().map(as_str).collect_vec(),
+        sorted.iter().rev().copied().collect_vec()
+    );
+
+    // Both ends meet without repeating a key
+    let mut iter = trie.prefix("").map(as_str);
+    assert_eq!(iter.next().unwrap(), "c");
+    assert_eq!(iter.next_back().unwrap(), "dog");
+    assert_eq!(iter.next_back().unwrap(), "cattle");
+    assert_eq!(iter.next().unwrap(), "ca");
+    assert_eq!(iter.collect_vec(), ["car", "cat"]);
+
+    let mut iter = trie.prefix("cat").map(as_str);
+    assert_eq!(iter.next_back().unwrap(), "cattle");
+    assert_eq!(iter.next().unwrap(), "cat");
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.next_back(), None);
+}
+
+#[test]
+fn range() {
+    let mut trie: Trie<String, _> = Trie::new();
+    let keys = ["car", "cat", "cattle", "ca", "cow", "dog", "c"];
+    for (value, &key) in keys.iter().enumerate() {
+        trie.insert(key, value);
+    }
+
+    let as_str = |(key, _): (Vec<u8>, _)| String::from_utf8(key).unwrap();
+    assert_eq!(
+        trie.range("cat".."cow").map(as_str).collect_vec(),
+        ["cat", "cattle"]
+    );
+    assert_eq!(
+        trie.range("cat"..="cow").rev().map(as_str).collect_vec(),
+        ["cow", "cattle", "cat"]
+    );
+    assert_eq!(trie.range("cb"..).map(as_str).collect_vec(), ["cow", "dog"]);
+    assert_eq!(trie.range(.."car").map(as_str).collect_vec(), ["c", "ca"]);
+    assert_eq!(
+        trie.range(.."car").rev().map(as_str).collect_vec(),
+        ["ca", "c"]
+    );
+    assert_eq!(
+        trie.range((Bound::Excluded("car"), Bound::Excluded("dog")))
+            .map(as_str)
+            .collect_vec(),
+        ["cat", "cattle", "cow"]
+    );
+    assert_eq!(trie.range("dog".."cat").count(), 0);
+    assert_eq!(trie.range::<str>(..).count(), keys.len());
+}
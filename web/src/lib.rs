@@ -1,12 +1,17 @@
 use std::io::BufRead;
 
-use grumpr::{librarian::query, Gram, Librarian, Library};
+use grumpr::{
+    Librarian, Library,
+    librarian::{Metadata, SortKey, pipeline::Pipeline, query},
+};
 use include_flate::flate;
-use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use strum::{EnumIter, IntoEnumIterator};
 use wasm_bindgen::prelude::*;
 
+/// Nodes a deep search may visit before it gives up, so a runaway query cannot hang the page.
+const SEARCH_NODES: usize = 20_000_000;
+
 #[wasm_bindgen(start)]
 pub fn initialise() {
     #[cfg(feature = "console_error_panic_hook")]
@@ -20,6 +25,21 @@ pub struct Match {
     pub depth: usize,
 }
 
+/// A crossword pattern, see [query::Pattern].
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Pattern {
+    pub pattern: String,
+    pub depth: usize,
+}
+
+/// A prefix to complete, see [query::Prefix].
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Complete {
+    pub prefix: String,
+}
+
 #[wasm_bindgen]
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 pub struct Filter {
@@ -44,12 +64,24 @@ pub struct Has {
     pub characters: String,
 }
 
+/// Guesses in Wordle and their marks, each written as `crane:gy..g`, see [query::Wordle::parse_guess].
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Wordle {
+    pub guesses: Vec<String>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 pub enum Command {
     Match(Match),
+    Pattern(Pattern),
+    Complete(Complete),
     Filter(Filter),
     Anagram(Anagram),
     Has(Has),
+    Wordle(Wordle),
+    /// A search in the query language, see [grumpr::librarian::pipeline].
+    Query(String),
 }
 
 #[wasm_bindgen(getter_with_clone)]
@@ -60,10 +92,10 @@ pub struct Seed {
     pub count: u64,
 }
 
-impl From<&grumpr::Seed> for Seed {
-    fn from(seed: &grumpr::Seed) -> Self {
+impl From<grumpr::Seed<'_>> for Seed {
+    fn from(seed: grumpr::Seed<'_>) -> Self {
         Seed {
-            root: seed.root.clone(),
+            root: seed.root.to_owned(),
             index: seed.index,
             count: seed.count,
         }
@@ -78,21 +110,55 @@ pub struct NGram {
     pub freq: f32,
 }
 
+/// The grams found by [process_results].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Results {
+    pub ngrams: Vec<NGram>,
+    /// `null` if the search ran to completion, otherwise the part of its budget it ran out of:
+    /// `"results"`, `"nodes"`, `"deadline"` or `"cancelled"`, see [query::Limit::as_str].
+    pub truncated: Option<&'static str>,
+}
+
+/// Run the commands and return an array of the grams found.
+///
+/// A search which runs out of budget returns the grams found so far,
+/// use [process_results] to tell whether it did.
 #[wasm_bindgen]
 pub fn process(library: Option<LibraryID>, commands: Box<[JsValue]>) -> Result<JsValue, String> {
+    let (ngrams, _) = process_ngrams(library, commands)?;
+    serde_wasm_bindgen::to_value(&ngrams).map_err(|err| err.to_string())
+}
+
+/// Run the commands as [process] does, and return the grams found with whether the search was cut short.
+#[wasm_bindgen]
+pub fn process_results(
+    library: Option<LibraryID>,
+    commands: Box<[JsValue]>,
+) -> Result<JsValue, String> {
+    let (ngrams, truncated) = process_ngrams(library, commands)?;
+    let results = Results {
+        ngrams,
+        truncated: truncated.map(|limit| limit.as_str()),
+    };
+    serde_wasm_bindgen::to_value(&results).map_err(|err| err.to_string())
+}
+
+fn process_ngrams(
+    library: Option<LibraryID>,
+    commands: Box<[JsValue]>,
+) -> Result<(Vec<NGram>, Option<query::Limit>), String> {
     let library: Library = library.unwrap_or_default().into();
     let commands = commands
         .into_iter()
-        .map(|js| serde_wasm_bindgen::from_value(js))
+        .map(serde_wasm_bindgen::from_value)
         .collect::<Result<Vec<Command>, _>>()
         .map_err(|err| err.to_string())?;
     let librarian = process_impl((&library).into(), commands).map_err(|err| err.to_string())?;
 
-    // Sort the grams
+    // Rank the grams
     let grams = librarian
-        .into_iter()
-        .sorted_by(Gram::cmp_by_count_mean)
-        .rev();
+        .top_k(librarian.len(), SortKey::CountMean)
+        .into_iter();
 
     // Convert into JS compatible format
     let mut total_count = 0;
@@ -112,7 +178,7 @@ pub fn process(library: Option<LibraryID>, commands: Box<[JsValue]>) -> Result<J
             freq: mean as f32 / total_count as f32,
         })
         .collect();
-    Ok(serde_wasm_bindgen::to_value(&ngrams).map_err(|err| err.to_string())?)
+    Ok((ngrams, librarian.truncated()))
 }
 
 fn process_impl<'l>(
@@ -144,26 +210,30 @@ fn process_impl<'l>(
                 }
 
                 if let Some(top) = filter.top {
-                    let seed = librarian
-                        .iter()
-                        .flat_map(|gram| gram.seeds())
-                        .sorted_by(|lhs, rhs| rhs.count.cmp(&lhs.count))
-                        .dedup_by(|lhs, rhs| lhs.index.eq(&rhs.index))
-                        .nth(top);
-                    if let Some(seed) = seed {
-                        let count = seed.count;
-                        librarian = if filter.invert {
-                            librarian.filter(|seed| seed.count <= count)
-                        } else {
-                            librarian.filter(|seed| seed.count > count)
-                        };
-                    }
+                    librarian = if filter.invert {
+                        librarian.without_top_seeds(top)
+                    } else {
+                        librarian.top_seeds(top)
+                    };
                 }
             }
             Command::Match(Match { pattern, depth }) => {
-                let query = query::Match::new(&pattern).depth(depth);
+                let query = query::Match::new(&pattern)
+                    .depth(depth)
+                    .budget(query::Budget::new().nodes(SEARCH_NODES));
                 librarian = librarian.search(&query)?;
             }
+            Command::Pattern(Pattern { pattern, depth }) => {
+                let query = query::Pattern::new(&pattern)
+                    .depth(depth)
+                    .budget(query::Budget::new().nodes(SEARCH_NODES));
+                librarian = librarian.pattern(&query)?;
+            }
+            Command::Complete(Complete { prefix }) => {
+                let query =
+                    query::Prefix::new(&prefix).budget(query::Budget::new().nodes(SEARCH_NODES));
+                librarian = librarian.complete(&query)?;
+            }
             Command::Anagram(Anagram {
                 pattern,
                 depth,
@@ -173,13 +243,28 @@ fn process_impl<'l>(
                 let query = query::Anagram::new(&pattern)
                     .depth(depth)
                     .partial(partial)
-                    .wildcards(wildcards);
+                    .wildcards(wildcards)
+                    .budget(query::Budget::new().nodes(SEARCH_NODES));
                 librarian = librarian.anagrams(&query)?;
             }
             Command::Has(Has { characters }) => {
                 let query = query::Has::new(&characters);
                 librarian = librarian.has(&query)?;
             }
+            Command::Wordle(Wordle { guesses }) => {
+                let mut query =
+                    query::Wordle::new().budget(query::Budget::new().nodes(SEARCH_NODES));
+                for guess in &guesses {
+                    let (word, marks) = query::Wordle::parse_guess(guess)
+                        .ok_or_else(|| format!("Invalid guess `{guess}`"))?;
+                    query = query.guess(word, marks);
+                }
+                librarian = librarian.wordle(&query)?;
+            }
+            Command::Query(input) => {
+                let pipeline = Pipeline::parse(&input)?;
+                librarian = librarian.run(&pipeline, &query::Budget::new().nodes(SEARCH_NODES))?;
+            }
         }
     }
 
@@ -241,6 +326,8 @@ impl From<LibraryID> for Library {
         let reader = match id {
             LibraryID::Google => LibraryID::Google.reader(),
         };
+        let (metadata, reader) =
+            Metadata::read_comments(reader).expect("Built-in libraries are read from memory");
         let mut rdr = csv::ReaderBuilder::new()
             .has_headers(false)
             .delimiter(b'\t')
@@ -257,6 +344,7 @@ impl From<LibraryID> for Library {
                 let Record { word, count } = result.ok()?;
                 Some((word, count))
             })
-            .collect()
+            .collect::<Library>()
+            .with_metadata(metadata)
     }
 }